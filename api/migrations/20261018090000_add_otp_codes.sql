@@ -0,0 +1,17 @@
+-- Add migration script here
+CREATE TABLE IF NOT EXISTS otp_codes (
+    id uuid DEFAULT gen_random_uuid() PRIMARY KEY,
+    user_id uuid NOT NULL,
+    code_hash VARCHAR(100) NOT NULL,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    issued_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    expires_at TIMESTAMP NOT NULL,
+    consumed_at TIMESTAMP,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS otp_codes_user_id_idx ON otp_codes (user_id, issued_at DESC);
+
+ALTER TABLE users ADD verified_at TIMESTAMP;
+UPDATE users SET verified_at = created_at WHERE verification_code IS NULL;
+ALTER TABLE users DROP COLUMN verification_code;
@@ -0,0 +1,3 @@
+-- Add migration script here
+ALTER TABLE sessions ADD user_agent TEXT;
+ALTER TABLE sessions ADD ip_address VARCHAR(45);
@@ -0,0 +1,16 @@
+-- Add migration script here
+CREATE TABLE IF NOT EXISTS ai_actions (
+    id uuid DEFAULT gen_random_uuid() PRIMARY KEY,
+    user_id uuid NOT NULL,
+    label VARCHAR(100) NOT NULL,
+    system_prompt TEXT NOT NULL,
+    temperature REAL,
+    model VARCHAR(100),
+    shared BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS ai_actions_user_id_idx ON ai_actions (user_id);
+CREATE INDEX IF NOT EXISTS ai_actions_shared_idx ON ai_actions (shared) WHERE shared;
@@ -0,0 +1,11 @@
+-- Add migration script here
+CREATE TABLE IF NOT EXISTS writing_styles (
+    user_id uuid PRIMARY KEY,
+    profile TEXT NOT NULL,
+    enabled BOOLEAN NOT NULL DEFAULT TRUE,
+    edited BOOLEAN NOT NULL DEFAULT FALSE,
+    docs_analyzed INTEGER NOT NULL DEFAULT 0,
+    generated_at TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
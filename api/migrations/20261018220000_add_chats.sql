@@ -0,0 +1,25 @@
+-- Add migration script here
+CREATE TABLE IF NOT EXISTS chat_threads (
+    id uuid DEFAULT gen_random_uuid() PRIMARY KEY,
+    user_id uuid NOT NULL,
+    doc_id uuid,
+    title VARCHAR(100),
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+    FOREIGN KEY (doc_id) REFERENCES docs(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS chat_threads_user_id_updated_at_idx ON chat_threads (user_id, updated_at DESC);
+
+CREATE TABLE IF NOT EXISTS chat_messages (
+    id uuid DEFAULT gen_random_uuid() PRIMARY KEY,
+    thread_id uuid NOT NULL,
+    role VARCHAR(20) NOT NULL CHECK (role IN ('user', 'assistant')),
+    content TEXT NOT NULL,
+    citations JSONB NOT NULL DEFAULT '[]',
+    created_at TIMESTAMP NOT NULL DEFAULT clock_timestamp(),
+    FOREIGN KEY (thread_id) REFERENCES chat_threads(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS chat_messages_thread_id_created_at_idx ON chat_messages (thread_id, created_at);
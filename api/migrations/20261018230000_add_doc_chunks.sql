@@ -0,0 +1,19 @@
+-- Add migration script here
+CREATE TABLE IF NOT EXISTS doc_chunks (
+    id uuid DEFAULT gen_random_uuid() PRIMARY KEY,
+    doc_id uuid NOT NULL,
+    position INT NOT NULL,
+    content TEXT NOT NULL,
+    model VARCHAR(100) NOT NULL,
+    embedding REAL[] NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (doc_id) REFERENCES docs(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS doc_chunks_doc_id_idx ON doc_chunks (doc_id);
+
+-- Embeddings are stored normalized, so their dot product is the cosine
+-- similarity. Plain arrays keep this working on servers without pgvector.
+CREATE OR REPLACE FUNCTION dot_product(a REAL[], b REAL[]) RETURNS DOUBLE PRECISION
+LANGUAGE SQL IMMUTABLE PARALLEL SAFE
+AS $$ SELECT sum(x::DOUBLE PRECISION * y::DOUBLE PRECISION) FROM unnest(a, b) AS t(x, y) $$;
@@ -0,0 +1,6 @@
+-- Add migration script here
+ALTER TABLE docs ADD COLUMN IF NOT EXISTS search tsvector GENERATED ALWAYS AS (
+    setweight(to_tsvector('english', title), 'A') || setweight(to_tsvector('english', content_text), 'B')
+) STORED;
+
+CREATE INDEX IF NOT EXISTS docs_search_idx ON docs USING GIN (search);
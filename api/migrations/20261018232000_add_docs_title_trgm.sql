@@ -0,0 +1,4 @@
+-- Add migration script here
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+CREATE INDEX IF NOT EXISTS docs_title_trgm_idx ON docs USING GIN (lower(title) gin_trgm_ops);
@@ -0,0 +1,22 @@
+-- Add migration script here
+CREATE TABLE IF NOT EXISTS doc_revisions (
+    id uuid DEFAULT gen_random_uuid() PRIMARY KEY,
+    doc_id uuid NOT NULL,
+    author_id uuid,
+    title VARCHAR(100) NOT NULL,
+    content_text TEXT NOT NULL,
+    content_json JSONB NOT NULL,
+    content_html TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (doc_id) REFERENCES docs(id) ON DELETE CASCADE,
+    FOREIGN KEY (author_id) REFERENCES users(id) ON DELETE SET NULL
+);
+
+CREATE INDEX IF NOT EXISTS doc_revisions_doc_id_created_at_idx ON doc_revisions (doc_id, created_at DESC);
+
+-- Start every existing document's history with its current content.
+INSERT INTO doc_revisions (doc_id, author_id, title, content_text, content_json, content_html, created_at, updated_at)
+SELECT id, user_id, title, content_text, content_json, content_html,
+    COALESCE(updated_at, created_at, CURRENT_TIMESTAMP), COALESCE(updated_at, created_at, CURRENT_TIMESTAMP)
+FROM docs;
@@ -0,0 +1,3 @@
+-- Add migration script here
+ALTER TABLE totp_factors ADD COLUMN IF NOT EXISTS failed_attempts INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE totp_factors ADD COLUMN IF NOT EXISTS locked_until TIMESTAMP;
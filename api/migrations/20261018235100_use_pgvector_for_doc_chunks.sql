@@ -0,0 +1,16 @@
+-- Add migration script here
+CREATE EXTENSION IF NOT EXISTS vector;
+
+-- Chunks embedded before this may not have 768 dimensions, so they are
+-- dropped instead of converted. The startup backfill re-embeds every
+-- document that has no chunks.
+DELETE FROM doc_chunks;
+
+ALTER TABLE doc_chunks ALTER COLUMN embedding TYPE vector(768) USING embedding::vector(768);
+
+DROP FUNCTION IF EXISTS dot_product(REAL[], REAL[]);
+
+-- Embeddings are stored normalized, so their inner product is the cosine
+-- similarity and nearest chunks can be found with `<#>`, the negative inner
+-- product.
+CREATE INDEX IF NOT EXISTS doc_chunks_embedding_idx ON doc_chunks USING hnsw (embedding vector_ip_ops);
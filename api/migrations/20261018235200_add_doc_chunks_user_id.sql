@@ -0,0 +1,10 @@
+-- Add migration script here
+ALTER TABLE doc_chunks ADD COLUMN IF NOT EXISTS user_id uuid;
+UPDATE doc_chunks SET user_id = docs.user_id FROM docs WHERE docs.id = doc_chunks.doc_id;
+ALTER TABLE doc_chunks ALTER COLUMN user_id SET NOT NULL;
+
+-- Searches only compare a user's own chunks. Filtering the shared HNSW index
+-- by user after the scan dropped results once other users' chunks filled the
+-- candidates it returns, so searches scan the user's chunks exactly instead.
+CREATE INDEX IF NOT EXISTS doc_chunks_user_id_model_idx ON doc_chunks (user_id, model);
+DROP INDEX IF EXISTS doc_chunks_embedding_idx;
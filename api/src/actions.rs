@@ -0,0 +1,145 @@
+use serde::Serialize;
+use sqlx::{PgPool, query};
+use uuid::Uuid;
+
+/// Longest `input`, such as a tone or language, accepted for an action.
+const MAX_INPUT_CHARS: usize = 100;
+
+/// A built-in action. The task is spliced into `builtin_prompt`; `{input}` is
+/// replaced with the value the client picked for actions that take one.
+struct Builtin {
+    id: &'static str,
+    label: &'static str,
+    task: &'static str,
+    temperature: Option<f32>,
+    input_label: Option<&'static str>,
+}
+
+const BUILTIN: &[Builtin] = &[
+    Builtin { id: "simplify", label: "Simplify", task: "simplify", temperature: None, input_label: None },
+    Builtin { id: "fix", label: "Fix spelling & grammar", task: "fix spelling and grammar", temperature: Some(0.2), input_label: None },
+    Builtin { id: "tone", label: "Rephrase with tone", task: "rephrase with {input} tone", temperature: None, input_label: Some("Tone") },
+    Builtin { id: "translate", label: "Translate", task: "translate to {input}", temperature: Some(0.2), input_label: Some("Language") },
+    Builtin { id: "shorter", label: "Make it shorter", task: "make it shorter", temperature: None, input_label: None },
+    Builtin { id: "longer", label: "Make it longer", task: "make it longer", temperature: None, input_label: None },
+    Builtin { id: "emojify", label: "Emojify", task: "emojify", temperature: None, input_label: None },
+    Builtin { id: "continue", label: "Continue writing", task: "continue from the selected text!", temperature: None, input_label: None },
+];
+
+fn builtin_prompt(task: &str) -> String {
+    format!(concat!(
+        "You are a helpful writing assistant. Please help the user to replace the selected text.\n\n",
+        "Your task is: {}\n\n",
+        "Make sure to provide only exact 1 option as a response."
+    ), task)
+}
+
+/// A named rewrite preset: the system prompt and settings `/prompt` runs the
+/// selected text through.
+///
+/// Built-in actions have short ids like `simplify`. Custom ones are created by
+/// users, have UUID ids and can be `shared` with everyone on the instance.
+#[derive(Serialize)]
+pub struct Action {
+    pub id: String,
+    pub label: String,
+    pub system_prompt: String,
+    pub temperature: Option<f32>,
+    pub model: Option<String>,
+    /// Set when the action needs an `input`, naming what to ask the user for.
+    pub input_label: Option<String>,
+    pub builtin: bool,
+    pub shared: bool,
+    /// Whether the current user may edit or delete the action.
+    pub owned: bool,
+}
+
+impl Action {
+    /// The system message for running this action, with `{input}` filled in.
+    pub fn system_message(&self, input: Option<&str>) -> Result<String, String> {
+        let input = input.map(str::trim).filter(|input| !input.is_empty());
+        if let Some(label) = &self.input_label {
+            if input.is_none() {
+                return Err(format!("{} is required for this action", label));
+            }
+        }
+        if input.is_some_and(|input| input.chars().count() > MAX_INPUT_CHARS) {
+            return Err(format!("Input must be at most {} characters", MAX_INPUT_CHARS));
+        }
+
+        Ok(self.system_prompt.replace("{input}", input.unwrap_or_default()))
+    }
+
+    pub fn custom(id: Uuid, label: String, system_prompt: String, temperature: Option<f32>, model: Option<String>, shared: bool, owned: bool) -> Action {
+        Action {
+            id: id.to_string(),
+            label,
+            system_prompt,
+            temperature,
+            model,
+            input_label: None,
+            builtin: false,
+            shared,
+            owned,
+        }
+    }
+}
+
+fn builtin() -> impl Iterator<Item = Action> {
+    BUILTIN.iter().map(|builtin| Action {
+        id: builtin.id.to_string(),
+        label: builtin.label.to_string(),
+        system_prompt: builtin_prompt(builtin.task),
+        temperature: builtin.temperature,
+        model: None,
+        input_label: builtin.input_label.map(str::to_string),
+        builtin: true,
+        shared: true,
+        owned: false,
+    })
+}
+
+/// Built-in actions followed by the user's own and shared custom ones.
+pub async fn list(pool: &PgPool, user_id: Uuid) -> Result<Vec<Action>, sqlx::Error> {
+    let custom = query!(
+        r#"
+        SELECT id, user_id, label, system_prompt, temperature, model, shared FROM ai_actions
+        WHERE user_id = $1 OR shared
+        ORDER BY lower(label), created_at
+        "#,
+        user_id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(builtin()
+        .chain(custom.into_iter().map(|action| {
+            Action::custom(action.id, action.label, action.system_prompt, action.temperature, action.model, action.shared, action.user_id == user_id)
+        }))
+        .collect())
+}
+
+/// Looks up an action the user may run by id.
+pub async fn find(pool: &PgPool, user_id: Uuid, id: &str) -> Result<Option<Action>, sqlx::Error> {
+    if let Some(action) = builtin().find(|action| action.id == id) {
+        return Ok(Some(action));
+    }
+    let Ok(id) = Uuid::parse_str(id) else {
+        return Ok(None);
+    };
+
+    let action = query!(
+        r#"
+        SELECT id, user_id, label, system_prompt, temperature, model, shared FROM ai_actions
+        WHERE id = $1 AND (user_id = $2 OR shared)
+        "#,
+        id,
+        user_id
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(action.map(|action| {
+        Action::custom(action.id, action.label, action.system_prompt, action.temperature, action.model, action.shared, action.user_id == user_id)
+    }))
+}
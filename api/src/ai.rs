@@ -0,0 +1,615 @@
+use axum::http::StatusCode;
+use futures_util::{stream::{self, BoxStream}, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::{collections::VecDeque, env::var, fmt};
+
+/// A chat message sent to the model.
+#[derive(Clone, Serialize)]
+pub struct Message {
+    pub role: String,
+    pub content: String,
+}
+
+impl Message {
+    pub fn system(content: String) -> Message {
+        Message { role: "system".to_string(), content }
+    }
+
+    pub fn user(content: String) -> Message {
+        Message { role: "user".to_string(), content }
+    }
+
+    pub fn assistant(content: String) -> Message {
+        Message { role: "assistant".to_string(), content }
+    }
+}
+
+/// Tokens the provider reports having used for a request.
+#[derive(Clone, Copy, Deserialize)]
+pub struct Usage {
+    pub prompt_tokens: i32,
+    pub completion_tokens: i32,
+}
+
+/// A chat request: the messages plus the model and sampling settings to run
+/// them with. `Provider::chat` fills in the configured model.
+pub struct Chat {
+    pub model: String,
+    pub temperature: Option<f32>,
+    pub messages: Vec<Message>,
+}
+
+pub struct Completion {
+    pub content: String,
+    pub usage: Option<Usage>,
+}
+
+/// A piece of a streamed completion. Usage, when the provider reports it,
+/// arrives once at the end.
+pub enum Chunk {
+    Content(String),
+    Usage(Usage),
+}
+
+#[derive(Debug)]
+pub enum AiError {
+    /// The provider answered with an error, such as a bad key or unknown model.
+    Provider(String),
+    /// The provider couldn't be reached or the connection dropped.
+    Request(String),
+    /// The provider answered with something that isn't a completion.
+    Decode(String),
+}
+
+impl AiError {
+    /// Status to answer the client with: errors from the provider are blamed
+    /// on the request, as they were before providers were configurable.
+    pub fn status(&self) -> StatusCode {
+        match self {
+            AiError::Provider(_) | AiError::Decode(_) => StatusCode::BAD_REQUEST,
+            AiError::Request(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+impl fmt::Display for AiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AiError::Provider(message) => write!(f, "{}", message),
+            AiError::Request(err) => write!(f, "{}", err),
+            AiError::Decode(err) => write!(f, "Failed to decode AI response: {}", err),
+        }
+    }
+}
+
+/// Runs chat completions against the backend picked by `AI_PROVIDER`,
+/// configured once at startup.
+///
+/// - `openai` talks to any OpenAI-compatible `/chat/completions` endpoint at
+///   `AI_BASE_URL`, authenticated with `AI_API_KEY`.
+/// - `ollama` talks to a local Ollama server's `/api/chat`.
+/// - `mock` answers without any network, echoing the last user message, for
+///   tests and offline development.
+///
+/// Without `AI_PROVIDER`, `openai` is used when an API key is set and `mock`
+/// otherwise.
+///
+/// Embeddings for semantic search come from the same backend with
+/// `AI_EMBEDDING_MODEL`, or from a local hashing embedder when that is
+/// `local`, which is also what the `mock` provider uses.
+///
+/// Custom actions may only pick `AI_MODEL` or one of the comma-separated
+/// `AI_ALLOWED_MODELS`, so users can't spend the operator's key on any model
+/// the upstream API offers.
+#[derive(Clone)]
+pub struct Provider {
+    model: String,
+    allowed_models: Vec<String>,
+    embedding_model: String,
+    backend: Backend,
+    client: reqwest::Client,
+}
+
+#[derive(Clone)]
+enum Backend {
+    OpenAi { base_url: String, api_key: Option<String>, reasoning_effort: Option<String> },
+    Ollama { base_url: String },
+    Mock,
+}
+
+pub fn provider() -> Provider {
+    // `ROUTER_AI_API_KEY` is what deployments used before providers were configurable.
+    let api_key = var("AI_API_KEY").or_else(|_| var("ROUTER_AI_API_KEY")).ok().filter(|key| !key.is_empty());
+
+    let backend = match var("AI_PROVIDER").ok().filter(|provider| !provider.is_empty()).as_deref() {
+        Some("openai") => openai_backend(api_key),
+        Some("ollama") => Backend::Ollama { base_url: base_url("http://localhost:11434") },
+        Some("mock") => Backend::Mock,
+        Some(other) => panic!("Unknown AI_PROVIDER {}, expected openai, ollama or mock", other),
+        None if api_key.is_some() => openai_backend(api_key),
+        None => {
+            eprintln!("AI_API_KEY is not set, using the mock AI provider");
+            Backend::Mock
+        }
+    };
+
+    let (default_model, default_embedding_model) = match backend {
+        Backend::OpenAi { .. } => ("basic.free", "text-embedding-3-small"),
+        Backend::Ollama { .. } => ("llama3.2", "nomic-embed-text"),
+        Backend::Mock => ("mock", LOCAL_EMBEDDING_MODEL),
+    };
+    Provider {
+        model: var("AI_MODEL").ok().filter(|model| !model.is_empty()).unwrap_or_else(|| default_model.to_string()),
+        allowed_models: var("AI_ALLOWED_MODELS")
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|model| !model.is_empty())
+            .map(str::to_string)
+            .collect(),
+        embedding_model: var("AI_EMBEDDING_MODEL").ok().filter(|model| !model.is_empty()).unwrap_or_else(|| default_embedding_model.to_string()),
+        backend,
+        client: reqwest::Client::new(),
+    }
+}
+
+const DEFAULT_OPENAI_BASE_URL: &str = "https://router.helpedby.ai/v1";
+
+fn openai_backend(api_key: Option<String>) -> Backend {
+    let base_url = base_url(DEFAULT_OPENAI_BASE_URL);
+    // The default router has always been sent `none`; other APIs may not
+    // know the parameter, so it is only sent to them when configured.
+    let reasoning_effort = var("AI_REASONING_EFFORT")
+        .ok()
+        .filter(|effort| !effort.is_empty())
+        .or_else(|| (base_url == DEFAULT_OPENAI_BASE_URL).then(|| "none".to_string()));
+    Backend::OpenAi { base_url, api_key, reasoning_effort }
+}
+
+/// `AI_BASE_URL` without a trailing slash, or the backend's default.
+fn base_url(default: &str) -> String {
+    var("AI_BASE_URL").ok().filter(|url| !url.is_empty()).unwrap_or_else(|| default.to_string()).trim_end_matches('/').to_string()
+}
+
+/// `AI_EMBEDDING_MODEL` value that selects the built-in embedder.
+const LOCAL_EMBEDDING_MODEL: &str = "local";
+/// Dimensions of every embedding, fixed by the `doc_chunks.embedding`
+/// column. OpenAI models are asked for vectors of this size; other models
+/// have to produce it natively.
+pub const EMBEDDING_DIMENSIONS: usize = 768;
+
+impl Provider {
+    /// The model requests use unless an action picks another.
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+
+    /// Whether actions may run with `model` instead of the default.
+    pub fn allows_model(&self, model: &str) -> bool {
+        model == self.model || self.allowed_models.iter().any(|allowed| allowed == model)
+    }
+
+    /// The model `embed` uses. Vectors from different models can't be
+    /// compared, so stored ones are tagged with it.
+    pub fn embedding_model(&self) -> &str {
+        &self.embedding_model
+    }
+
+    pub fn chat(&self, messages: Vec<Message>) -> Chat {
+        Chat { model: self.model.clone(), temperature: None, messages }
+    }
+
+    pub async fn complete(&self, chat: Chat) -> Result<Completion, AiError> {
+        match &self.backend {
+            Backend::OpenAi { .. } => {
+                let text = self.send(chat, false).await?.text().await.map_err(|err| AiError::Request(err.to_string()))?;
+                let resp = serde_json::from_str::<OpenAiResponse>(&text).map_err(|err| AiError::Decode(err.to_string()))?;
+                Ok(Completion {
+                    content: resp.choices.into_iter().next().and_then(|choice| choice.message.content).unwrap_or_default(),
+                    usage: resp.usage,
+                })
+            }
+            Backend::Ollama { .. } => {
+                let text = self.send(chat, false).await?.text().await.map_err(|err| AiError::Request(err.to_string()))?;
+                let resp = serde_json::from_str::<OllamaResponse>(&text).map_err(|err| AiError::Decode(err.to_string()))?;
+                Ok(Completion {
+                    usage: resp.usage(),
+                    content: resp.message.map(|message| message.content).unwrap_or_default(),
+                })
+            }
+            Backend::Mock => {
+                let (content, usage) = mock_reply(&chat.messages);
+                Ok(Completion { content, usage: Some(usage) })
+            }
+        }
+    }
+
+    /// Streams a completion. Dropping the stream closes the connection to
+    /// the provider, cancelling the generation.
+    pub async fn stream(&self, chat: Chat) -> Result<BoxStream<'static, Result<Chunk, AiError>>, AiError> {
+        match &self.backend {
+            Backend::OpenAi { .. } => Ok(lines(self.send(chat, true).await?, parse_openai_line)),
+            Backend::Ollama { .. } => Ok(lines(self.send(chat, true).await?, parse_ollama_line)),
+            Backend::Mock => {
+                let (content, usage) = mock_reply(&chat.messages);
+                let mut chunks: Vec<_> = content.split_inclusive(' ').map(|word| Ok(Chunk::Content(word.to_string()))).collect();
+                chunks.push(Ok(Chunk::Usage(usage)));
+                Ok(stream::iter(chunks).boxed())
+            }
+        }
+    }
+
+    /// Embeds each of `inputs` as a unit-length vector.
+    pub async fn embed(&self, inputs: Vec<String>) -> Result<Vec<Vec<f32>>, AiError> {
+        if inputs.is_empty() {
+            return Ok(Vec::new());
+        }
+        let local = self.embedding_model == LOCAL_EMBEDDING_MODEL || matches!(self.backend, Backend::Mock);
+        let vectors = match &self.backend {
+            _ if local => inputs.iter().map(|input| local_embedding(input)).collect(),
+            Backend::OpenAi { base_url, api_key, .. } => {
+                let request = self.client.post(format!("{}/embeddings", base_url)).json(&EmbeddingRequest {
+                    model: &self.embedding_model,
+                    input: &inputs,
+                    dimensions: Some(EMBEDDING_DIMENSIONS),
+                });
+                let request = match api_key {
+                    Some(api_key) => request.bearer_auth(api_key),
+                    None => request,
+                };
+                let text = check(request.send().await).await?.text().await.map_err(|err| AiError::Request(err.to_string()))?;
+                let mut resp = serde_json::from_str::<OpenAiEmbeddingResponse>(&text).map_err(|err| AiError::Decode(err.to_string()))?;
+                resp.data.sort_by_key(|embedding| embedding.index);
+                resp.data.into_iter().map(|embedding| embedding.embedding).collect()
+            }
+            Backend::Ollama { base_url } => {
+                let request = self.client.post(format!("{}/api/embed", base_url)).json(&EmbeddingRequest {
+                    model: &self.embedding_model,
+                    input: &inputs,
+                    dimensions: None,
+                });
+                let text = check(request.send().await).await?.text().await.map_err(|err| AiError::Request(err.to_string()))?;
+                serde_json::from_str::<OllamaEmbeddingResponse>(&text).map_err(|err| AiError::Decode(err.to_string()))?.embeddings
+            }
+            Backend::Mock => unreachable!("the mock provider always embeds locally"),
+        };
+
+        if vectors.len() != inputs.len() {
+            return Err(AiError::Decode(format!("expected {} embeddings, got {}", inputs.len(), vectors.len())));
+        }
+        if let Some(vector) = vectors.iter().find(|vector| vector.len() != EMBEDDING_DIMENSIONS) {
+            return Err(AiError::Decode(format!("expected {}-dimensional embeddings from {}, got {}", EMBEDDING_DIMENSIONS, self.embedding_model, vector.len())));
+        }
+        Ok(vectors.into_iter().map(normalize).collect())
+    }
+
+    /// Posts a chat request, turning error statuses into `AiError::Provider`.
+    async fn send(&self, chat: Chat, stream: bool) -> Result<reqwest::Response, AiError> {
+        let request = match &self.backend {
+            Backend::OpenAi { base_url, api_key, reasoning_effort } => {
+                let request = self.client.post(format!("{}/chat/completions", base_url)).json(&OpenAiRequest {
+                    model: &chat.model,
+                    messages: chat.messages,
+                    stream,
+                    temperature: chat.temperature,
+                    stream_options: stream.then_some(OpenAiStreamOptions { include_usage: true }),
+                    reasoning: reasoning_effort.as_deref().map(|effort| OpenAiReasoning { effort }),
+                });
+                match api_key {
+                    Some(api_key) => request.bearer_auth(api_key),
+                    None => request,
+                }
+            }
+            Backend::Ollama { base_url } => self.client.post(format!("{}/api/chat", base_url)).json(&OllamaRequest {
+                model: &chat.model,
+                messages: chat.messages,
+                stream,
+                options: OllamaOptions { temperature: chat.temperature },
+            }),
+            Backend::Mock => unreachable!("the mock provider doesn't send requests"),
+        };
+
+        check(request.send().await).await
+    }
+}
+
+/// Turns failed requests and error statuses into `AiError`s.
+async fn check(resp: Result<reqwest::Response, reqwest::Error>) -> Result<reqwest::Response, AiError> {
+    let resp = resp.map_err(|err| AiError::Request(err.to_string()))?;
+    if resp.status().is_success() {
+        return Ok(resp);
+    }
+    let text = resp.text().await.unwrap_or_default();
+    let message = serde_json::from_str::<ErrorResponse>(&text).map(|err| err.error.into_message()).unwrap_or(text);
+    Err(AiError::Provider(message))
+}
+
+/// A deterministic bag-of-words embedding: each lowercased word and its
+/// character trigrams are hashed into a fixed number of buckets. It only
+/// captures shared vocabulary, but needs no model or network.
+fn local_embedding(text: &str) -> Vec<f32> {
+    let mut vector = vec![0.0; EMBEDDING_DIMENSIONS];
+    let mut add = |feature: &str, weight: f32| {
+        let hash = fnv1a(feature.as_bytes());
+        let sign = if hash & 1 == 0 { 1.0 } else { -1.0 };
+        vector[(hash >> 1) as usize % EMBEDDING_DIMENSIONS] += sign * weight;
+    };
+    for word in text.split(|c: char| !c.is_alphanumeric()).filter(|word| !word.is_empty()) {
+        let word = word.to_lowercase();
+        add(&word, 1.0);
+        let padded: Vec<char> = format!(" {} ", word).chars().collect();
+        for trigram in padded.windows(3) {
+            add(&trigram.iter().collect::<String>(), 0.5);
+        }
+    }
+    vector
+}
+
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| (hash ^ *byte as u64).wrapping_mul(0x100000001b3))
+}
+
+/// Scales a vector to unit length, so a dot product is a cosine similarity.
+fn normalize(mut vector: Vec<f32>) -> Vec<f32> {
+    let norm = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        vector.iter_mut().for_each(|x| *x /= norm);
+    }
+    vector
+}
+
+/// Echoes the last user message, counting a token per word.
+fn mock_reply(messages: &[Message]) -> (String, Usage) {
+    let content = messages.iter().rev().find(|message| message.role == "user").map(|message| message.content.clone()).unwrap_or_default();
+    let words = |text: &str| text.split_whitespace().count() as i32;
+    let usage = Usage {
+        prompt_tokens: messages.iter().map(|message| words(&message.content)).sum(),
+        completion_tokens: words(&content),
+    };
+    (content, usage)
+}
+
+type ParseLine = fn(&str) -> Result<Vec<Chunk>, AiError>;
+
+/// Splits a streamed response body into lines and parses each into chunks.
+fn lines(resp: reqwest::Response, parse: ParseLine) -> BoxStream<'static, Result<Chunk, AiError>> {
+    struct State {
+        upstream: BoxStream<'static, Result<Vec<u8>, reqwest::Error>>,
+        buffer: Vec<u8>,
+        pending: VecDeque<Result<Chunk, AiError>>,
+        finished: bool,
+    }
+
+    impl State {
+        fn parse(&mut self, line: &[u8], parse: ParseLine) {
+            let line = String::from_utf8_lossy(line);
+            match parse(line.trim()) {
+                Ok(chunks) => self.pending.extend(chunks.into_iter().map(Ok)),
+                Err(err) => {
+                    self.pending.push_back(Err(err));
+                    self.finished = true;
+                }
+            }
+        }
+    }
+
+    let state = State {
+        upstream: resp.bytes_stream().map(|bytes| bytes.map(|bytes| bytes.to_vec())).boxed(),
+        buffer: Vec::new(),
+        pending: VecDeque::new(),
+        finished: false,
+    };
+    stream::unfold(state, move |mut state| async move {
+        loop {
+            if let Some(chunk) = state.pending.pop_front() {
+                return Some((chunk, state));
+            }
+            if state.finished {
+                return None;
+            }
+            match state.upstream.next().await {
+                Some(Ok(bytes)) => {
+                    state.buffer.extend_from_slice(&bytes);
+                    while !state.finished {
+                        let Some(end) = state.buffer.iter().position(|byte| *byte == b'\n') else {
+                            break;
+                        };
+                        let line: Vec<u8> = state.buffer.drain(..=end).collect();
+                        state.parse(&line, parse);
+                    }
+                }
+                Some(Err(err)) => {
+                    state.pending.push_back(Err(AiError::Request(err.to_string())));
+                    state.finished = true;
+                }
+                None => {
+                    let line = std::mem::take(&mut state.buffer);
+                    state.parse(&line, parse);
+                    state.finished = true;
+                }
+            }
+        }
+    })
+    .boxed()
+}
+
+/// Parses a line of an OpenAI server-sent event stream.
+fn parse_openai_line(line: &str) -> Result<Vec<Chunk>, AiError> {
+    let Some(data) = line.strip_prefix("data:").map(str::trim) else {
+        return Ok(Vec::new());
+    };
+    if data == "[DONE]" {
+        return Ok(Vec::new());
+    }
+
+    let chunk = serde_json::from_str::<OpenAiStreamChunk>(data).map_err(|err| AiError::Decode(err.to_string()))?;
+    if let Some(err) = chunk.error {
+        return Err(AiError::Provider(err.into_message()));
+    }
+    let mut chunks: Vec<Chunk> = chunk
+        .choices
+        .into_iter()
+        .filter_map(|choice| choice.delta.content)
+        .filter(|content| !content.is_empty())
+        .map(Chunk::Content)
+        .collect();
+    chunks.extend(chunk.usage.map(Chunk::Usage));
+    Ok(chunks)
+}
+
+/// Parses a line of Ollama's newline-delimited JSON stream.
+fn parse_ollama_line(line: &str) -> Result<Vec<Chunk>, AiError> {
+    if line.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let chunk = serde_json::from_str::<OllamaResponse>(line).map_err(|err| AiError::Decode(err.to_string()))?;
+    if let Some(err) = chunk.error {
+        return Err(AiError::Provider(err));
+    }
+    let mut chunks: Vec<Chunk> = chunk
+        .message
+        .as_ref()
+        .map(|message| message.content.clone())
+        .filter(|content| !content.is_empty())
+        .map(Chunk::Content)
+        .into_iter()
+        .collect();
+    chunks.extend(chunk.usage().map(Chunk::Usage));
+    Ok(chunks)
+}
+
+#[derive(Serialize)]
+struct OpenAiRequest<'a> {
+    model: &'a str,
+    messages: Vec<Message>,
+    stream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stream_options: Option<OpenAiStreamOptions>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reasoning: Option<OpenAiReasoning<'a>>,
+}
+
+#[derive(Serialize)]
+struct OpenAiStreamOptions {
+    include_usage: bool,
+}
+
+#[derive(Serialize)]
+struct OpenAiReasoning<'a> {
+    effort: &'a str,
+}
+
+#[derive(Deserialize)]
+struct OpenAiResponse {
+    choices: Vec<OpenAiChoice>,
+    usage: Option<Usage>,
+}
+
+#[derive(Deserialize)]
+struct OpenAiChoice {
+    message: OpenAiMessage,
+}
+
+#[derive(Deserialize)]
+struct OpenAiMessage {
+    content: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct OpenAiStreamChunk {
+    #[serde(default)]
+    choices: Vec<OpenAiStreamChoice>,
+    usage: Option<Usage>,
+    error: Option<ErrorBody>,
+}
+
+#[derive(Deserialize)]
+struct OpenAiStreamChoice {
+    delta: OpenAiMessage,
+}
+
+#[derive(Serialize)]
+struct EmbeddingRequest<'a> {
+    model: &'a str,
+    input: &'a [String],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dimensions: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct OpenAiEmbeddingResponse {
+    data: Vec<OpenAiEmbedding>,
+}
+
+#[derive(Deserialize)]
+struct OpenAiEmbedding {
+    index: usize,
+    embedding: Vec<f32>,
+}
+
+#[derive(Deserialize)]
+struct OllamaEmbeddingResponse {
+    embeddings: Vec<Vec<f32>>,
+}
+
+#[derive(Serialize)]
+struct OllamaRequest<'a> {
+    model: &'a str,
+    messages: Vec<Message>,
+    stream: bool,
+    options: OllamaOptions,
+}
+
+#[derive(Serialize)]
+struct OllamaOptions {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+}
+
+#[derive(Deserialize)]
+struct OllamaResponse {
+    message: Option<OllamaMessage>,
+    #[serde(default)]
+    done: bool,
+    prompt_eval_count: Option<i32>,
+    eval_count: Option<i32>,
+    error: Option<String>,
+}
+
+impl OllamaResponse {
+    fn usage(&self) -> Option<Usage> {
+        self.done.then(|| Usage {
+            prompt_tokens: self.prompt_eval_count.unwrap_or_default(),
+            completion_tokens: self.eval_count.unwrap_or_default(),
+        })
+    }
+}
+
+#[derive(Deserialize)]
+struct OllamaMessage {
+    content: String,
+}
+
+/// OpenAI-compatible servers nest an object under `error`; Ollama sends a string.
+#[derive(Deserialize)]
+struct ErrorResponse {
+    error: ErrorBody,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ErrorBody {
+    Object { message: String },
+    Message(String),
+}
+
+impl ErrorBody {
+    fn into_message(self) -> String {
+        match self {
+            ErrorBody::Object { message } | ErrorBody::Message(message) => message,
+        }
+    }
+}
@@ -3,24 +3,32 @@ use axum::{
     response::IntoResponse,
     extract::{ Request, Json },
     http,
-    http::{ Response, StatusCode },
+    http::{ Method, Response, StatusCode },
     middleware::Next,
 };
+use chrono::{ Duration, Utc };
 use jsonwebtoken::{
     decode,
-    DecodingKey,
+    decode_header,
+    encode,
+    Header,
     TokenData,
     Validation
 };
-use serde::{ Deserialize, Serialize };
+use serde::{ de::DeserializeOwned, Deserialize, Serialize };
 use serde_json::json;
 use sqlx::{PgPool, query};
+use uuid::Uuid;
+
+use crate::keys;
+use crate::session::{self, access_token_ttl_minutes};
 
 #[derive(Serialize, Deserialize)]
 pub struct Claims {
     pub exp: usize,
     pub iat: usize,
     pub email: String,
+    pub sid: String,
 }
 
 pub struct AuthError {
@@ -28,10 +36,53 @@ pub struct AuthError {
     pub status_code: StatusCode,
 }
 
+/// Prefix that tells personal access tokens apart from JWTs.
+pub const PAT_PREFIX: &str = "nt_";
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Scope {
+    /// Signed-in session, allowed everywhere.
+    Full,
+    /// Personal access token that may only read documents.
+    Read,
+    /// Personal access token that may read and modify documents.
+    Write,
+}
+
+impl Scope {
+    pub fn from_db(value: &str) -> Scope {
+        match value {
+            "write" => Scope::Write,
+            _ => Scope::Read,
+        }
+    }
+
+    pub fn as_db(&self) -> &'static str {
+        match self {
+            Scope::Full => "full",
+            Scope::Read => "read",
+            Scope::Write => "write",
+        }
+    }
+
+    fn allows(&self, method: &Method, path: &str) -> bool {
+        let docs = path == "/docs" || path.starts_with("/docs/");
+        match self {
+            Scope::Full => true,
+            Scope::Write => docs,
+            Scope::Read => docs && (method == Method::GET || method == Method::HEAD),
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct CurrentUser {
     pub email: String,
     pub id: String,
+    /// Set when authenticated with a session JWT rather than a personal access token.
+    pub session_id: Option<String>,
+    pub scope: Scope,
 }
 
 impl IntoResponse for AuthError {
@@ -58,7 +109,29 @@ pub async fn authorize(mut req: Request, next: Next) -> Result<Response<Body>, A
     };
     let mut header = auth_header.split_whitespace();
     let (_, token) = (header.next(), header.next());
-    let token_data = match decode_jwt(token.unwrap().to_string()) {
+    let token = token.unwrap_or_default().to_string();
+
+    let pool = req.extensions().get::<PgPool>().unwrap().clone();
+
+    let auth_user = if token.starts_with(PAT_PREFIX) {
+        authorize_pat(&pool, &token).await?
+    } else {
+        authorize_jwt(&pool, token).await?
+    };
+
+    if !auth_user.scope.allows(req.method(), req.uri().path()) {
+        return Err(AuthError {
+            message: "Token scope does not allow this request".to_string(),
+            status_code: StatusCode::FORBIDDEN
+        });
+    }
+
+    req.extensions_mut().insert(auth_user);
+    Ok(next.run(req).await)
+}
+
+async fn authorize_jwt(pool: &PgPool, token: String) -> Result<CurrentUser, AuthError> {
+    let token_data = match decode_jwt(token) {
         Ok(data) => data,
         Err(_) => return Err(AuthError {
             message: "Unable to decode token".to_string(),
@@ -66,48 +139,126 @@ pub async fn authorize(mut req: Request, next: Next) -> Result<Response<Body>, A
         }),
     };
 
-    let pool = req.extensions().get::<PgPool>().unwrap();
+    let session_id = Uuid::parse_str(&token_data.claims.sid).map_err(|_| AuthError {
+        message: "Unable to decode token".to_string(),
+        status_code: StatusCode::UNAUTHORIZED
+    })?;
 
     let user = query!(
         r#"
-        SELECT * FROM users WHERE email = $1
+        SELECT users.id, users.email, users.verified_at, sessions.revoked_at
+        FROM sessions
+        JOIN users ON users.id = sessions.user_id
+        WHERE sessions.id = $1 AND users.email = $2
         "#,
+        session_id,
         &token_data.claims.email
     )
     .fetch_one(pool);
 
     match user.await {
         Ok(user) => {
-            if user.verification_code.is_some() {
+            if user.revoked_at.is_some() {
+                return Err(AuthError {
+                    message: "Session has been revoked".to_string(),
+                    status_code: StatusCode::UNAUTHORIZED
+                });
+            }
+
+            if user.verified_at.is_none() {
                 return Err(AuthError {
                     message: "User is not verified".to_string(),
                     status_code: StatusCode::UNAUTHORIZED
                 });
             }
 
-            let auth_user = CurrentUser {
+            if session::touch(pool, session_id).await.is_err() {
+                return Err(AuthError {
+                    message: "Failed to update session".to_string(),
+                    status_code: StatusCode::INTERNAL_SERVER_ERROR
+                });
+            }
+
+            Ok(CurrentUser {
                 email: user.email.to_string(),
                 id: user.id.to_string(),
-            };
-            req.extensions_mut().insert(auth_user);
-            Ok(next.run(req).await)
-        }
-        Err(_) => {
-            return Err(AuthError {
-                message: "User not found".to_string(),
-                status_code: StatusCode::UNAUTHORIZED
-            });
+                session_id: Some(session_id.to_string()),
+                scope: Scope::Full,
+            })
         }
+        Err(_) => Err(AuthError {
+            message: "User not found".to_string(),
+            status_code: StatusCode::UNAUTHORIZED
+        }),
     }
 }
 
-pub fn decode_jwt(jwt_token: String) -> Result<TokenData<Claims>, StatusCode> {
-    let secret: String = std::env::var("SECRET").expect("SECRET must be set").as_str().to_string();
-    let result: Result<TokenData<Claims>, StatusCode> = decode(
-        &jwt_token,
-        &DecodingKey::from_secret(secret.as_ref()),
-        &Validation::default(),
+async fn authorize_pat(pool: &PgPool, token: &str) -> Result<CurrentUser, AuthError> {
+    let user = query!(
+        r#"
+        UPDATE personal_access_tokens SET last_used_at = NOW()
+        FROM users
+        WHERE users.id = personal_access_tokens.user_id
+            AND personal_access_tokens.token_hash = $1
+            AND personal_access_tokens.revoked_at IS NULL
+            AND (personal_access_tokens.expires_at IS NULL OR personal_access_tokens.expires_at > NOW())
+            AND users.verified_at IS NOT NULL
+        RETURNING users.id, users.email, personal_access_tokens.scope
+        "#,
+        session::hash_token(token)
     )
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR);
-    result
+    .fetch_optional(pool)
+    .await
+    .map_err(|_| AuthError {
+        message: "Failed to verify token".to_string(),
+        status_code: StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    match user {
+        Some(user) => Ok(CurrentUser {
+            email: user.email,
+            id: user.id.to_string(),
+            session_id: None,
+            scope: Scope::from_db(&user.scope),
+        }),
+        None => Err(AuthError {
+            message: "Invalid or expired token".to_string(),
+            status_code: StatusCode::UNAUTHORIZED
+        }),
+    }
+}
+
+pub fn decode_jwt(jwt_token: String) -> Result<TokenData<Claims>, StatusCode> {
+    decode_token(&jwt_token)
+}
+
+pub fn encode_jwt(email: String, sid: String) -> Result<String, StatusCode> {
+    let now = Utc::now();
+    let expire: chrono::TimeDelta = Duration::minutes(access_token_ttl_minutes());
+    let exp: usize = (now + expire).timestamp() as usize;
+    let iat: usize = now.timestamp() as usize;
+    let claim = Claims { iat, exp, email, sid };
+
+    encode_token(&claim)
+}
+
+/// Signs any claims with the current signing key.
+pub fn encode_token<T: Serialize>(claims: &T) -> Result<String, StatusCode> {
+    let (kid, algorithm, key) = keys::get().encoding();
+    let mut header = Header::new(algorithm);
+    header.kid = kid;
+
+    encode(&header, claims, key)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+/// Verifies a token signed by [`encode_token`] against the key named by its `kid`.
+pub fn decode_token<T: DeserializeOwned>(token: &str) -> Result<TokenData<T>, StatusCode> {
+    let header = decode_header(token).map_err(|_| StatusCode::UNAUTHORIZED)?;
+    let (algorithm, key) = keys::get()
+        .decoding(header.kid.as_deref())
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+
+    decode(token, key, &Validation::new(algorithm))
+        .map_err(|_| StatusCode::UNAUTHORIZED)
 }
@@ -0,0 +1,339 @@
+use serde::Serialize;
+use serde_json::Value;
+use similar::{capture_diff_slices_deadline, Algorithm, ChangeTag, DiffOp, TextDiff};
+use std::time::{Duration, Instant};
+
+/// Time a diff may take before it settles for a coarser result, so huge
+/// documents can't tie up a request.
+const DIFF_TIMEOUT: Duration = Duration::from_secs(1);
+/// Unchanged lines shown around each hunk of the text diff.
+const CONTEXT_LINES: usize = 3;
+
+/// A block of a TipTap document that holds text rather than other blocks,
+/// such as a paragraph, heading or code block, wherever it is nested.
+struct Block {
+    kind: String,
+    /// Types of the blocks it sits in, outermost first, such as
+    /// `["bulletList", "listItem"]`.
+    context: Vec<String>,
+    /// Indices of the nodes leading to it from the document root.
+    path: Vec<usize>,
+    text: String,
+    /// The node and its context, serialized, so blocks that differ in any
+    /// way, formatting included, compare unequal.
+    key: String,
+}
+
+/// How a block changed between two versions of a document.
+#[derive(Serialize)]
+pub struct BlockChange {
+    /// `inserted`, `deleted` or `changed`.
+    pub op: &'static str,
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub context: Vec<String>,
+    pub old_path: Option<Vec<usize>>,
+    pub new_path: Option<Vec<usize>>,
+    pub old_text: Option<String>,
+    pub new_text: Option<String>,
+    /// Word by word changes of a `changed` block's text. All `equal` when
+    /// only its formatting or attributes changed.
+    pub inline: Vec<InlineChange>,
+}
+
+#[derive(Serialize)]
+pub struct InlineChange {
+    /// `equal`, `insert` or `delete`.
+    pub op: &'static str,
+    pub text: String,
+}
+
+/// Compares two TipTap documents block by block. Blocks are matched up in
+/// order; one replaced by a block of the same type counts as changed, with
+/// the differences in its text, anything else as deleted and inserted.
+pub fn blocks(old: &Value, new: &Value) -> Vec<BlockChange> {
+    let old = flatten(old);
+    let new = flatten(new);
+    let old_keys: Vec<&str> = old.iter().map(|block| block.key.as_str()).collect();
+    let new_keys: Vec<&str> = new.iter().map(|block| block.key.as_str()).collect();
+
+    let mut changes = Vec::new();
+    for op in capture_diff_slices_deadline(Algorithm::Myers, &old_keys, &new_keys, Some(Instant::now() + DIFF_TIMEOUT)) {
+        match op {
+            DiffOp::Equal { .. } => {}
+            DiffOp::Delete { old_index, old_len, .. } => {
+                changes.extend(old[old_index..old_index + old_len].iter().map(deleted));
+            }
+            DiffOp::Insert { new_index, new_len, .. } => {
+                changes.extend(new[new_index..new_index + new_len].iter().map(inserted));
+            }
+            DiffOp::Replace { old_index, old_len, new_index, new_len } => {
+                let old = &old[old_index..old_index + old_len];
+                let new = &new[new_index..new_index + new_len];
+                for i in 0..old_len.max(new_len) {
+                    match (old.get(i), new.get(i)) {
+                        (Some(old), Some(new)) if old.kind == new.kind => changes.push(changed(old, new)),
+                        (old, new) => {
+                            changes.extend(old.map(deleted));
+                            changes.extend(new.map(inserted));
+                        }
+                    }
+                }
+            }
+        }
+    }
+    changes
+}
+
+/// A unified diff of two texts, line by line, labelled with `old_name` and
+/// `new_name`. Empty when they are the same.
+pub fn text(old: &str, new: &str, old_name: &str, new_name: &str) -> String {
+    TextDiff::configure()
+        .timeout(DIFF_TIMEOUT)
+        .diff_lines(old, new)
+        .unified_diff()
+        .context_radius(CONTEXT_LINES)
+        .header(old_name, new_name)
+        .to_string()
+}
+
+fn deleted(block: &Block) -> BlockChange {
+    BlockChange {
+        op: "deleted",
+        kind: block.kind.clone(),
+        context: block.context.clone(),
+        old_path: Some(block.path.clone()),
+        new_path: None,
+        old_text: Some(block.text.clone()),
+        new_text: None,
+        inline: Vec::new(),
+    }
+}
+
+fn inserted(block: &Block) -> BlockChange {
+    BlockChange {
+        op: "inserted",
+        kind: block.kind.clone(),
+        context: block.context.clone(),
+        old_path: None,
+        new_path: Some(block.path.clone()),
+        old_text: None,
+        new_text: Some(block.text.clone()),
+        inline: Vec::new(),
+    }
+}
+
+fn changed(old: &Block, new: &Block) -> BlockChange {
+    let mut inline: Vec<InlineChange> = Vec::new();
+    let diff = TextDiff::configure().timeout(DIFF_TIMEOUT).diff_words(old.text.as_str(), new.text.as_str());
+    for change in diff.iter_all_changes() {
+        let op = match change.tag() {
+            ChangeTag::Equal => "equal",
+            ChangeTag::Insert => "insert",
+            ChangeTag::Delete => "delete",
+        };
+        match inline.last_mut() {
+            Some(last) if last.op == op => last.text.push_str(change.value()),
+            _ => inline.push(InlineChange { op, text: change.value().to_string() }),
+        }
+    }
+
+    BlockChange {
+        op: "changed",
+        kind: new.kind.clone(),
+        context: new.context.clone(),
+        old_path: Some(old.path.clone()),
+        new_path: Some(new.path.clone()),
+        old_text: Some(old.text.clone()),
+        new_text: Some(new.text.clone()),
+        inline,
+    }
+}
+
+/// The text blocks of a document in order.
+fn flatten(doc: &Value) -> Vec<Block> {
+    let mut blocks = Vec::new();
+    let mut context = Vec::new();
+    let mut path = Vec::new();
+    for (index, node) in children(doc).iter().enumerate() {
+        path.push(index);
+        collect(node, &mut context, &mut path, &mut blocks);
+        path.pop();
+    }
+    blocks
+}
+
+fn collect(node: &Value, context: &mut Vec<String>, path: &mut Vec<usize>, blocks: &mut Vec<Block>) {
+    let kind = node.get("type").and_then(Value::as_str).unwrap_or_default().to_string();
+    let content = children(node);
+    if content.iter().all(is_inline) {
+        blocks.push(Block {
+            key: format!("{}\n{}", context.join("/"), node),
+            text: content.iter().map(inline_text).collect(),
+            kind,
+            context: context.clone(),
+            path: path.clone(),
+        });
+        return;
+    }
+
+    context.push(kind);
+    for (index, child) in content.iter().enumerate() {
+        path.push(index);
+        collect(child, context, path, blocks);
+        path.pop();
+    }
+    context.pop();
+}
+
+fn children(node: &Value) -> &[Value] {
+    node.get("content").and_then(Value::as_array).map(Vec::as_slice).unwrap_or_default()
+}
+
+fn is_inline(node: &Value) -> bool {
+    matches!(node.get("type").and_then(Value::as_str), Some("text" | "hardBreak"))
+}
+
+fn inline_text(node: &Value) -> &str {
+    match node.get("type").and_then(Value::as_str) {
+        Some("hardBreak") => "\n",
+        _ => node.get("text").and_then(Value::as_str).unwrap_or_default(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn paragraph(text: &str) -> Value {
+        json!({ "type": "paragraph", "content": [{ "type": "text", "text": text }] })
+    }
+
+    fn doc(content: Vec<Value>) -> Value {
+        json!({ "type": "doc", "content": content })
+    }
+
+    fn list(items: &[Value]) -> Value {
+        json!({
+            "type": "bulletList",
+            "content": items.iter().map(|item| json!({ "type": "listItem", "content": [item] })).collect::<Vec<_>>(),
+        })
+    }
+
+    /// The old and new text a word diff describes.
+    fn sides(inline: &[InlineChange]) -> (String, String) {
+        let side = |skip: &str| inline.iter().filter(|change| change.op != skip).map(|change| change.text.as_str()).collect();
+        (side("insert"), side("delete"))
+    }
+
+    #[test]
+    fn unchanged_documents_have_no_changes() {
+        let doc = doc(vec![paragraph("Hello"), list(&[paragraph("One")])]);
+        assert!(blocks(&doc, &doc).is_empty());
+    }
+
+    #[test]
+    fn paragraph_edit_is_a_word_diff() {
+        let changes = blocks(&doc(vec![paragraph("Hello world")]), &doc(vec![paragraph("Hello there world")]));
+        assert_eq!(changes.len(), 1);
+        let change = &changes[0];
+        assert_eq!(change.op, "changed");
+        assert_eq!(change.kind, "paragraph");
+        assert_eq!(change.old_path, Some(vec![0]));
+        assert_eq!(change.new_path, Some(vec![0]));
+        assert_eq!(sides(&change.inline), ("Hello world".to_string(), "Hello there world".to_string()));
+        assert!(change.inline.iter().all(|change| change.op != "delete"));
+        assert!(change.inline.iter().any(|change| change.op == "insert" && change.text.contains("there")));
+    }
+
+    #[test]
+    fn inserted_block() {
+        let changes = blocks(
+            &doc(vec![paragraph("First"), paragraph("Last")]),
+            &doc(vec![paragraph("First"), paragraph("Middle"), paragraph("Last")]),
+        );
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].op, "inserted");
+        assert_eq!(changes[0].new_path, Some(vec![1]));
+        assert_eq!(changes[0].new_text.as_deref(), Some("Middle"));
+        assert_eq!(changes[0].old_path, None);
+    }
+
+    #[test]
+    fn deleted_block() {
+        let changes = blocks(
+            &doc(vec![paragraph("First"), paragraph("Middle"), paragraph("Last")]),
+            &doc(vec![paragraph("First"), paragraph("Last")]),
+        );
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].op, "deleted");
+        assert_eq!(changes[0].old_path, Some(vec![1]));
+        assert_eq!(changes[0].old_text.as_deref(), Some("Middle"));
+        assert_eq!(changes[0].new_path, None);
+    }
+
+    #[test]
+    fn formatting_change_keeps_the_text() {
+        let bold = doc(vec![json!({
+            "type": "paragraph",
+            "content": [{ "type": "text", "text": "Important", "marks": [{ "type": "bold" }] }],
+        })]);
+        let changes = blocks(&doc(vec![paragraph("Important")]), &bold);
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].op, "changed");
+        assert_eq!(changes[0].old_text, changes[0].new_text);
+        assert!(changes[0].inline.iter().all(|change| change.op == "equal"));
+    }
+
+    #[test]
+    fn block_replaced_by_another_type_is_deleted_and_inserted() {
+        let heading = json!({ "type": "heading", "attrs": { "level": 1 }, "content": [{ "type": "text", "text": "Title" }] });
+        let changes = blocks(&doc(vec![paragraph("Title")]), &doc(vec![heading]));
+        let ops: Vec<_> = changes.iter().map(|change| (change.op, change.kind.as_str())).collect();
+        assert_eq!(ops, [("deleted", "paragraph"), ("inserted", "heading")]);
+    }
+
+    #[test]
+    fn nested_lists_are_flattened_with_their_context() {
+        let nested = json!({
+            "type": "listItem",
+            "content": [paragraph("Two"), list(&[paragraph("Two a")])],
+        });
+        let doc = doc(vec![
+            paragraph("Intro"),
+            json!({ "type": "bulletList", "content": [{ "type": "listItem", "content": [paragraph("One")] }, nested] }),
+        ]);
+
+        let blocks = flatten(&doc);
+        let flat: Vec<_> = blocks.iter().map(|block| (block.text.as_str(), block.path.clone(), block.context.join("/"))).collect();
+        assert_eq!(flat, [
+            ("Intro", vec![0], String::new()),
+            ("One", vec![1, 0, 0], "bulletList/listItem".to_string()),
+            ("Two", vec![1, 1, 0], "bulletList/listItem".to_string()),
+            ("Two a", vec![1, 1, 1, 0, 0], "bulletList/listItem/bulletList/listItem".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn nested_list_item_edit() {
+        let old = doc(vec![list(&[paragraph("One"), paragraph("Two")])]);
+        let new = doc(vec![list(&[paragraph("One"), paragraph("Two and a half")])]);
+        let changes = blocks(&old, &new);
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].op, "changed");
+        assert_eq!(changes[0].context, ["bulletList", "listItem"]);
+        assert_eq!(changes[0].new_path, Some(vec![0, 1, 0]));
+        assert_eq!(sides(&changes[0].inline), ("Two".to_string(), "Two and a half".to_string()));
+    }
+
+    #[test]
+    fn same_text_in_another_list_is_a_change() {
+        // Moving a paragraph into a list changes its context, so it doesn't
+        // count as unchanged.
+        let changes = blocks(&doc(vec![paragraph("Item")]), &doc(vec![list(&[paragraph("Item")])]));
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].op, "changed");
+        assert_eq!(changes[0].context, ["bulletList", "listItem"]);
+    }
+}
@@ -0,0 +1,190 @@
+use sqlx::{PgPool, query};
+use std::fmt;
+use uuid::Uuid;
+
+use crate::ai::{AiError, Provider};
+use crate::retrieval;
+use crate::usage;
+
+/// Chunks sent to the provider per embedding request.
+const BATCH_SIZE: usize = 32;
+
+pub enum IndexError {
+    Database(sqlx::Error),
+    Ai(AiError),
+}
+
+impl From<sqlx::Error> for IndexError {
+    fn from(err: sqlx::Error) -> IndexError {
+        IndexError::Database(err)
+    }
+}
+
+impl From<AiError> for IndexError {
+    fn from(err: AiError) -> IndexError {
+        IndexError::Ai(err)
+    }
+}
+
+impl fmt::Display for IndexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IndexError::Database(err) => write!(f, "{}", err),
+            IndexError::Ai(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+/// Splits a document into chunks and replaces its stored embeddings with
+/// fresh ones from the provider's embedding model.
+///
+/// Embedding is slow, so if the document is saved again in the meantime the
+/// result is dropped and left to the indexing of the newer save. Returns the
+/// number of characters sent to the provider.
+pub async fn index(pool: &PgPool, provider: &Provider, doc_id: Uuid) -> Result<usize, IndexError> {
+    let Some(doc) = query!(
+        r#"
+        SELECT title, content_text FROM docs WHERE id = $1
+        "#,
+        doc_id
+    )
+    .fetch_optional(pool)
+    .await? else {
+        return Ok(0);
+    };
+
+    let mut chunks = retrieval::chunks(&doc.content_text);
+    if chunks.is_empty() && !doc.title.trim().is_empty() {
+        chunks.push(String::new());
+    }
+    // The title goes with every chunk, as it is often what a search is after.
+    let inputs: Vec<String> = chunks.iter().map(|chunk| format!("{}\n\n{}", doc.title, chunk).trim().to_string()).collect();
+    let chars = inputs.iter().map(|input| input.chars().count()).sum();
+    let mut embeddings = Vec::with_capacity(inputs.len());
+    for batch in inputs.chunks(BATCH_SIZE) {
+        embeddings.extend(provider.embed(batch.to_vec()).await?);
+    }
+
+    let mut tx = pool.begin().await?;
+    let current = query!(
+        r#"
+        SELECT user_id, title, content_text FROM docs WHERE id = $1 FOR UPDATE
+        "#,
+        doc_id
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+    let Some(current) = current.filter(|current| current.title == doc.title && current.content_text == doc.content_text) else {
+        return Ok(chars);
+    };
+
+    query!(
+        r#"
+        DELETE FROM doc_chunks WHERE doc_id = $1
+        "#,
+        doc_id
+    )
+    .execute(&mut *tx)
+    .await?;
+    for (position, (chunk, embedding)) in chunks.into_iter().zip(embeddings).enumerate() {
+        query!(
+            r#"
+            INSERT INTO doc_chunks (doc_id, user_id, position, content, model, embedding) VALUES ($1, $2, $3, $4, $5, $6::real[]::vector)
+            "#,
+            doc_id,
+            current.user_id,
+            position as i32,
+            chunk,
+            provider.embedding_model(),
+            &embedding
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+    tx.commit().await?;
+    Ok(chars)
+}
+
+/// Indexes a document in the background, so saving it doesn't wait on the
+/// embedding provider.
+///
+/// Indexing counts against the user's AI quota. Once that is used up the
+/// document's chunks are dropped instead, so searches don't match text it no
+/// longer has, and the next start's backfill indexes it again.
+pub fn spawn_index(pool: PgPool, provider: Provider, user_id: Uuid, doc_id: Uuid) {
+    tokio::spawn(async move {
+        let usage_id = match usage::reserve(&pool, user_id, provider.embedding_model()).await {
+            Ok(Ok(usage_id)) => usage_id,
+            Ok(Err(reason)) => {
+                eprintln!("Not indexing doc {}: {}", doc_id, reason);
+                if let Err(err) = clear(&pool, doc_id).await {
+                    eprintln!("Failed to clear chunks of doc {}: {}", doc_id, err);
+                }
+                return;
+            }
+            Err(err) => {
+                eprintln!("Failed to check AI usage: {}", err);
+                return;
+            }
+        };
+
+        match index(&pool, &provider, doc_id).await {
+            Ok(chars) => {
+                if let Err(err) = usage::record(&pool, usage_id, usage::estimate_tokens(chars), 0).await {
+                    eprintln!("Failed to record AI usage: {}", err);
+                }
+            }
+            Err(err) => eprintln!("Failed to index doc {}: {}", doc_id, err),
+        }
+    });
+}
+
+async fn clear(pool: &PgPool, doc_id: Uuid) -> Result<(), sqlx::Error> {
+    query!(
+        r#"
+        DELETE FROM doc_chunks WHERE doc_id = $1
+        "#,
+        doc_id
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Indexes, in the background, documents that have no embeddings from the
+/// current model: ones saved before semantic search existed or while the
+/// provider was unreachable, or all of them after the model is changed.
+pub fn spawn_backfill(pool: PgPool, provider: Provider) {
+    tokio::spawn(async move {
+        let docs = query!(
+            r#"
+            SELECT id FROM docs
+            WHERE length(trim(title || content_text)) > 0
+                AND NOT EXISTS (SELECT 1 FROM doc_chunks WHERE doc_id = docs.id AND model = $1)
+            ORDER BY updated_at DESC
+            "#,
+            provider.embedding_model()
+        )
+        .fetch_all(&pool)
+        .await;
+        let docs = match docs {
+            Ok(docs) => docs,
+            Err(err) => {
+                eprintln!("Failed to find docs to index: {}", err);
+                return;
+            }
+        };
+
+        for doc in docs {
+            if let Err(err) = index(&pool, &provider, doc.id).await {
+                eprintln!("Failed to index doc {}: {}", doc.id, err);
+                // Most likely the provider is down or has no such model;
+                // the remaining docs are picked up on the next start.
+                if matches!(err, IndexError::Ai(_)) {
+                    return;
+                }
+            }
+        }
+    });
+}
@@ -0,0 +1,208 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use jsonwebtoken::{
+    jwk::{
+        AlgorithmParameters,
+        CommonParameters,
+        EllipticCurve,
+        Jwk,
+        JwkSet,
+        KeyAlgorithm,
+        OctetKeyPairParameters,
+        OctetKeyPairType,
+        PublicKeyUse,
+        RSAKeyParameters,
+        RSAKeyType,
+    },
+    Algorithm,
+    DecodingKey,
+    EncodingKey,
+};
+use openssl::pkey::{Id, PKey, Private, Public};
+use std::{env::var, fs, path::Path, sync::OnceLock};
+
+static KEYS: OnceLock<JwtKeys> = OnceLock::new();
+
+/// Keys used to sign and verify access tokens, loaded once at startup.
+///
+/// With `JWT_KEYS_DIR` set, every `<kid>.pem` (or `<kid>.pub.pem`) in the
+/// directory is an Ed25519 (EdDSA) or RSA (RS256) key. Private keys can sign,
+/// public keys only verify, and `JWT_SIGNING_KID` picks the key new tokens are
+/// signed with. To rotate, add the new key, switch `JWT_SIGNING_KID` and
+/// keep the old key in the directory until the tokens it signed have expired.
+///
+/// Without `JWT_KEYS_DIR` tokens are signed with HS256 and `SECRET`. When both
+/// are configured, `SECRET` is still accepted for verifying tokens without a
+/// `kid`, so switching to asymmetric keys doesn't log anyone out.
+pub struct JwtKeys {
+    signing: SigningKey,
+    verifying: Vec<VerifyingKey>,
+    jwks: JwkSet,
+}
+
+struct SigningKey {
+    kid: Option<String>,
+    algorithm: Algorithm,
+    key: EncodingKey,
+}
+
+struct VerifyingKey {
+    kid: Option<String>,
+    algorithm: Algorithm,
+    key: DecodingKey,
+}
+
+pub fn init() {
+    let keys = match var("JWT_KEYS_DIR").ok().filter(|dir| !dir.is_empty()) {
+        Some(dir) => JwtKeys::from_dir(Path::new(&dir)),
+        None => JwtKeys::from_secret(),
+    };
+    if KEYS.set(keys).is_err() {
+        panic!("JWT keys are already initialized");
+    }
+}
+
+pub fn get() -> &'static JwtKeys {
+    KEYS.get().expect("JWT keys are not initialized")
+}
+
+impl JwtKeys {
+    fn from_secret() -> JwtKeys {
+        let secret = var("SECRET")
+            .ok()
+            .filter(|secret| !secret.is_empty())
+            .expect("SECRET or JWT_KEYS_DIR must be set");
+        JwtKeys {
+            signing: SigningKey {
+                kid: None,
+                algorithm: Algorithm::HS256,
+                key: EncodingKey::from_secret(secret.as_ref()),
+            },
+            verifying: vec![legacy_key(&secret)],
+            jwks: JwkSet { keys: vec![] },
+        }
+    }
+
+    fn from_dir(dir: &Path) -> JwtKeys {
+        let mut signing_keys = Vec::new();
+        let mut verifying = Vec::new();
+        let mut jwks = Vec::new();
+
+        let mut entries: Vec<_> = fs::read_dir(dir)
+            .expect("Failed to read JWT_KEYS_DIR")
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "pem"))
+            .collect();
+        entries.sort();
+
+        for path in entries {
+            let stem = path.file_stem().unwrap().to_string_lossy();
+            let kid = stem.strip_suffix(".pub").unwrap_or(&stem).to_string();
+            let pem = fs::read(&path).expect("Failed to read JWT key");
+
+            let (algorithm, jwk) = match PKey::private_key_from_pem(&pem) {
+                Ok(private) => {
+                    let (algorithm, jwk) = public_jwk(&kid, &to_public(&private));
+                    let key = match algorithm {
+                        Algorithm::EdDSA => EncodingKey::from_ed_pem(&pem),
+                        _ => EncodingKey::from_rsa_pem(&pem),
+                    }
+                    .unwrap_or_else(|err| panic!("Invalid JWT key {}: {}", path.display(), err));
+                    signing_keys.push(SigningKey { kid: Some(kid.clone()), algorithm, key });
+                    (algorithm, jwk)
+                }
+                Err(_) => {
+                    let public = PKey::public_key_from_pem(&pem)
+                        .unwrap_or_else(|err| panic!("Invalid JWT key {}: {}", path.display(), err));
+                    public_jwk(&kid, &public)
+                }
+            };
+
+            let key = DecodingKey::from_jwk(&jwk)
+                .unwrap_or_else(|err| panic!("Invalid JWT key {}: {}", path.display(), err));
+            verifying.push(VerifyingKey { kid: Some(kid), algorithm, key });
+            jwks.push(jwk);
+        }
+
+        let signing_kid = var("JWT_SIGNING_KID").ok().filter(|kid| !kid.is_empty());
+        let signing = match signing_kid {
+            Some(kid) => signing_keys
+                .into_iter()
+                .find(|key| key.kid.as_deref() == Some(kid.as_str()))
+                .unwrap_or_else(|| panic!("No private key {}.pem in JWT_KEYS_DIR", kid)),
+            None if signing_keys.len() == 1 => signing_keys.remove(0),
+            None => panic!("JWT_SIGNING_KID must be set when JWT_KEYS_DIR has several private keys"),
+        };
+
+        if let Some(secret) = var("SECRET").ok().filter(|secret| !secret.is_empty()) {
+            verifying.push(legacy_key(&secret));
+        }
+
+        JwtKeys { signing, verifying, jwks: JwkSet { keys: jwks } }
+    }
+
+    pub fn encoding(&self) -> (Option<String>, Algorithm, &EncodingKey) {
+        (self.signing.kid.clone(), self.signing.algorithm, &self.signing.key)
+    }
+
+    pub fn decoding(&self, kid: Option<&str>) -> Option<(Algorithm, &DecodingKey)> {
+        self.verifying
+            .iter()
+            .find(|key| key.kid.as_deref() == kid)
+            .map(|key| (key.algorithm, &key.key))
+    }
+
+    pub fn jwks(&self) -> &JwkSet {
+        &self.jwks
+    }
+}
+
+fn legacy_key(secret: &str) -> VerifyingKey {
+    VerifyingKey {
+        kid: None,
+        algorithm: Algorithm::HS256,
+        key: DecodingKey::from_secret(secret.as_ref()),
+    }
+}
+
+fn to_public(private: &PKey<Private>) -> PKey<Public> {
+    let der = private.public_key_to_der().expect("Failed to derive public key");
+    PKey::public_key_from_der(&der).expect("Failed to derive public key")
+}
+
+fn public_jwk(kid: &str, key: &PKey<Public>) -> (Algorithm, Jwk) {
+    let (algorithm, key_algorithm, parameters) = match key.id() {
+        Id::ED25519 => (
+            Algorithm::EdDSA,
+            KeyAlgorithm::EdDSA,
+            AlgorithmParameters::OctetKeyPair(OctetKeyPairParameters {
+                key_type: OctetKeyPairType::OctetKeyPair,
+                curve: EllipticCurve::Ed25519,
+                x: URL_SAFE_NO_PAD.encode(key.raw_public_key().expect("Invalid Ed25519 key")),
+            }),
+        ),
+        Id::RSA => {
+            let rsa = key.rsa().expect("Invalid RSA key");
+            (
+                Algorithm::RS256,
+                KeyAlgorithm::RS256,
+                AlgorithmParameters::RSA(RSAKeyParameters {
+                    key_type: RSAKeyType::RSA,
+                    n: URL_SAFE_NO_PAD.encode(rsa.n().to_vec()),
+                    e: URL_SAFE_NO_PAD.encode(rsa.e().to_vec()),
+                }),
+            )
+        }
+        _ => panic!("JWT key {} must be an Ed25519 or RSA key", kid),
+    };
+
+    let jwk = Jwk {
+        common: CommonParameters {
+            public_key_use: Some(PublicKeyUse::Signature),
+            key_algorithm: Some(key_algorithm),
+            key_id: Some(kid.to_string()),
+            ..Default::default()
+        },
+        algorithm: parameters,
+    };
+    (algorithm, jwk)
+}
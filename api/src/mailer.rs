@@ -0,0 +1,133 @@
+use lettre::{
+    message::{header::ContentType, Mailbox, MultiPart, SinglePart},
+    transport::smtp::authentication::Credentials,
+    AsyncFileTransport,
+    AsyncSmtpTransport,
+    AsyncTransport,
+    Message,
+    Tokio1Executor,
+};
+use std::{env::var, fmt, path::PathBuf};
+
+/// An email ready to send, with a plain-text part for clients that don't
+/// render HTML.
+pub struct Email {
+    pub subject: String,
+    pub text: String,
+    pub html: String,
+}
+
+/// Sends account email through the transport picked by `MAIL_TRANSPORT`,
+/// configured once at startup.
+///
+/// - `smtp` relays through `EMAIL_HOST`, using `EMAIL_SECURITY` (`starttls`,
+///   `tls` or `none`) and `EMAIL_PORT`.
+/// - `file` writes each message as an `.eml` file into `MAIL_DIR`.
+/// - `stdout` prints each message, for local development and tests.
+///
+/// Without `MAIL_TRANSPORT`, SMTP is used when `EMAIL_HOST` is set and stdout
+/// otherwise.
+#[derive(Clone)]
+pub struct Mailer {
+    from: Mailbox,
+    transport: Transport,
+}
+
+#[derive(Clone)]
+enum Transport {
+    Smtp(AsyncSmtpTransport<Tokio1Executor>),
+    File(PathBuf),
+    Stdout,
+}
+
+#[derive(Debug)]
+pub enum MailError {
+    InvalidAddress(String),
+    Send(String),
+}
+
+impl fmt::Display for MailError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MailError::InvalidAddress(address) => write!(f, "Invalid email address {}", address),
+            MailError::Send(err) => write!(f, "Could not send email: {}", err),
+        }
+    }
+}
+
+pub fn mailer() -> Mailer {
+    let from = var("EMAIL_FROM")
+        .ok()
+        .filter(|from| !from.is_empty())
+        .unwrap_or_else(|| "Notes <noreply@localhost>".to_string())
+        .parse()
+        .expect("EMAIL_FROM must be a valid address");
+
+    let transport = match var("MAIL_TRANSPORT").ok().filter(|transport| !transport.is_empty()).as_deref() {
+        Some("smtp") => smtp_transport(),
+        Some("file") => file_transport(),
+        Some("stdout") => Transport::Stdout,
+        Some(other) => panic!("Unknown MAIL_TRANSPORT {}, expected smtp, file or stdout", other),
+        None if var("EMAIL_HOST").is_ok_and(|host| !host.is_empty()) => smtp_transport(),
+        None => {
+            eprintln!("EMAIL_HOST is not set, printing emails to stdout");
+            Transport::Stdout
+        }
+    };
+
+    Mailer { from, transport }
+}
+
+fn smtp_transport() -> Transport {
+    let host = var("EMAIL_HOST").ok().filter(|host| !host.is_empty()).expect("EMAIL_HOST must be set for the smtp transport");
+    let security = var("EMAIL_SECURITY").ok().filter(|security| !security.is_empty()).unwrap_or_else(|| "starttls".to_string());
+    let (builder, default_port) = match security.as_str() {
+        "starttls" => (AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&host), 587),
+        "tls" => (AsyncSmtpTransport::<Tokio1Executor>::relay(&host), 465),
+        "none" => (Ok(AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&host)), 25),
+        other => panic!("Unknown EMAIL_SECURITY {}, expected starttls, tls or none", other),
+    };
+    let port = var("EMAIL_PORT").ok().and_then(|v| v.parse().ok()).unwrap_or(default_port);
+    let mut builder = builder.expect("Invalid EMAIL_HOST").port(port);
+
+    if let (Some(user), Some(pass)) = (var("EMAIL_USER").ok().filter(|user| !user.is_empty()), var("EMAIL_PASS").ok()) {
+        builder = builder.credentials(Credentials::new(user, pass));
+    }
+    Transport::Smtp(builder.build())
+}
+
+fn file_transport() -> Transport {
+    let dir = PathBuf::from(var("MAIL_DIR").ok().filter(|dir| !dir.is_empty()).unwrap_or_else(|| "mail".to_string()));
+    std::fs::create_dir_all(&dir).expect("Failed to create MAIL_DIR");
+    Transport::File(dir)
+}
+
+impl Mailer {
+    pub async fn send(&self, to: &str, email: Email) -> Result<(), MailError> {
+        let to: Mailbox = to.parse().map_err(|_| MailError::InvalidAddress(to.to_string()))?;
+        if let Transport::Stdout = self.transport {
+            // Print the readable part rather than the encoded message, so
+            // codes and links can be copied straight from the log.
+            println!("From: {}\nTo: {}\nSubject: {}\n\n{}", self.from, to, email.subject, email.text);
+            return Ok(());
+        }
+
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(to)
+            .subject(email.subject)
+            .multipart(
+                MultiPart::alternative()
+                    .singlepart(SinglePart::builder().header(ContentType::TEXT_PLAIN).body(email.text))
+                    .singlepart(SinglePart::builder().header(ContentType::TEXT_HTML).body(email.html))
+            )
+            .map_err(|err| MailError::Send(err.to_string()))?;
+
+        let sent = match &self.transport {
+            Transport::Smtp(transport) => transport.send(message).await.map(|_| ()).map_err(|err| err.to_string()),
+            Transport::File(dir) => AsyncFileTransport::<Tokio1Executor>::new(dir).send(message).await.map(|_| ()).map_err(|err| err.to_string()),
+            Transport::Stdout => Ok(()),
+        };
+        sent.map_err(MailError::Send)
+    }
+}
@@ -1,18 +1,37 @@
+mod actions;
+mod ai;
 mod auth;
+mod diff;
+mod embeddings;
+mod keys;
+mod mailer;
+mod oidc;
+mod outbox;
+mod passkey;
+mod ratelimit;
+mod retrieval;
+mod revisions;
 mod routes;
+mod session;
+mod style;
+mod templates;
+mod totp;
+mod usage;
 
 use axum::{
     middleware,
-    routing::{post, get},
+    routing::{delete, post, get, put},
     Extension, Router,
 };
 use dotenvy::dotenv;
 use sqlx::postgres::PgPool;
+use std::net::SocketAddr;
 use tower_http::cors::CorsLayer;
 
 #[tokio::main]
 async fn main() {
     dotenv().expect("Failed to load .env file");
+    keys::init();
 
     let pool = PgPool::connect(
             std::env::var("DATABASE_URL").expect("DATABASE_URL must be set").as_str()
@@ -20,24 +39,134 @@ async fn main() {
         .await
         .expect("Failed to create pool");
 
+    let outbox = outbox::Outbox::new(pool.clone());
+    outbox.spawn_worker(mailer::mailer());
+    let limiter = ratelimit::limiter(&pool);
+    let provider = ai::provider();
+    embeddings::spawn_backfill(pool.clone(), provider.clone());
+
     let app = Router::new()
-        .route("/otp", post(routes::otp::handler))
-        .route("/otp-verify", post(routes::otpverify::handler))
-        .route("/prompt", post(routes::prompt::handler))
+        .route("/.well-known/jwks.json", get(routes::jwks::handler))
+        .route("/otp",
+            post(routes::otp::handler)
+            .layer(middleware::from_fn_with_state(limiter.clone(), ratelimit::limit)))
+        .route("/otp-verify",
+            post(routes::otpverify::handler)
+            .layer(middleware::from_fn_with_state(limiter.clone(), ratelimit::limit)))
+        .route("/magic-link", post(routes::magiclink::handler))
+        .route("/passkey/login/start", post(routes::passkeylogin::start_handler))
+        .route("/passkey/login/finish", post(routes::passkeylogin::finish_handler))
+        .route("/oidc/login/start", post(routes::oidclogin::start_handler))
+        .route("/oidc/login/finish", post(routes::oidclogin::finish_handler))
+        .route("/emails/{email_id}", get(routes::emaildetails::get_handler))
+        .route("/token/refresh", post(routes::tokenrefresh::handler))
+        .route("/logout",
+            post(routes::logout::handler)
+            .layer(middleware::from_fn(auth::authorize)))
+        .route("/prompt",
+            post(routes::prompt::handler)
+            .layer(middleware::from_fn(auth::authorize)))
+        .route("/prompt/stream",
+            post(routes::prompt::stream_handler)
+            .layer(middleware::from_fn(auth::authorize)))
+        .route("/ai/actions",
+            get(routes::aiactions::get_handler)
+            .post(routes::aiactions::post_handler)
+            .layer(middleware::from_fn(auth::authorize)))
+        .route("/ai/actions/{action_id}",
+            put(routes::aiactiondetails::put_handler)
+            .delete(routes::aiactiondetails::delete_handler)
+            .layer(middleware::from_fn(auth::authorize)))
         .route("/me",
-            get(routes::me::handler)
+            get(routes::me::get_handler)
+            .put(routes::me::put_handler)
+            .layer(middleware::from_fn(auth::authorize)))
+        .route("/me/style",
+            get(routes::style::get_handler)
+            .put(routes::style::put_handler)
+            .layer(middleware::from_fn(auth::authorize)))
+        .route("/me/style/regenerate",
+            post(routes::styleregenerate::post_handler)
+            .layer(middleware::from_fn(auth::authorize)))
+        .route("/me/usage",
+            get(routes::usage::get_handler)
+            .layer(middleware::from_fn(auth::authorize)))
+        .route("/me/sessions",
+            get(routes::sessions::get_handler)
+            .delete(routes::sessions::delete_handler)
+            .layer(middleware::from_fn(auth::authorize)))
+        .route("/me/sessions/{session_id}",
+            delete(routes::sessiondetails::delete_handler)
+            .layer(middleware::from_fn(auth::authorize)))
+        .route("/me/tokens",
+            get(routes::tokens::get_handler)
+            .post(routes::tokens::post_handler)
+            .layer(middleware::from_fn(auth::authorize)))
+        .route("/me/tokens/{token_id}",
+            delete(routes::tokendetails::delete_handler)
+            .layer(middleware::from_fn(auth::authorize)))
+        .route("/me/passkeys",
+            get(routes::passkeys::get_handler)
+            .layer(middleware::from_fn(auth::authorize)))
+        .route("/me/passkeys/register/start",
+            post(routes::passkeyregister::start_handler)
+            .layer(middleware::from_fn(auth::authorize)))
+        .route("/me/passkeys/register/finish",
+            post(routes::passkeyregister::finish_handler)
+            .layer(middleware::from_fn(auth::authorize)))
+        .route("/me/passkeys/{passkey_id}",
+            delete(routes::passkeydetails::delete_handler)
+            .layer(middleware::from_fn(auth::authorize)))
+        .route("/me/totp",
+            get(routes::totp::get_handler)
+            .post(routes::totp::post_handler)
+            .delete(routes::totp::delete_handler)
+            .layer(middleware::from_fn(auth::authorize)))
+        .route("/me/totp/confirm",
+            post(routes::totpconfirm::post_handler)
+            .layer(middleware::from_fn(auth::authorize)))
+        .route("/me/totp/recovery-codes",
+            post(routes::totprecoverycodes::post_handler)
             .layer(middleware::from_fn(auth::authorize)))
         .route("/docs",
             get(routes::docs::get_handler).post(routes::docs::post_handler)
             .layer(middleware::from_fn(auth::authorize)))
+        .route("/chats",
+            get(routes::chats::get_handler)
+            .post(routes::chats::post_handler)
+            .layer(middleware::from_fn(auth::authorize)))
+        .route("/chats/{thread_id}",
+            get(routes::chatdetails::get_handler)
+            .delete(routes::chatdetails::delete_handler)
+            .layer(middleware::from_fn(auth::authorize)))
+        .route("/chats/{thread_id}/messages",
+            post(routes::chatmessages::post_handler)
+            .layer(middleware::from_fn(auth::authorize)))
         .route("/docs/{doc_id}",
             get(routes::docdetails::get_handler)
             .put(routes::docdetails::put_handler)
             .delete(routes::docdetails::delete_handler)
             .layer(middleware::from_fn(auth::authorize)))
+        .route("/docs/{doc_id}/revisions",
+            get(routes::revisions::get_handler)
+            .layer(middleware::from_fn(auth::authorize)))
+        .route("/docs/{doc_id}/revisions/diff",
+            get(routes::revisiondiff::get_handler)
+            .layer(middleware::from_fn(auth::authorize)))
+        .route("/docs/{doc_id}/revisions/{revision_id}",
+            get(routes::revisiondetails::get_handler)
+            .layer(middleware::from_fn(auth::authorize)))
+        .route("/docs/{doc_id}/revisions/{revision_id}/restore",
+            post(routes::revisionrestore::post_handler)
+            .layer(middleware::from_fn(auth::authorize)))
         .layer(CorsLayer::permissive())
-        .layer(Extension(pool));
+        .layer(Extension(pool))
+        .layer(Extension(passkey::webauthn()))
+        .layer(Extension(oidc::provider()))
+        .layer(Extension(outbox))
+        .layer(Extension(provider))
+        .layer(Extension(templates::templates()));
 
     let listener = tokio::net::TcpListener::bind("0.0.0.0:4012").await.unwrap();
-    axum::serve(listener, app).await.unwrap();
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await.unwrap();
 }
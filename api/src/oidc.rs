@@ -0,0 +1,176 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use jsonwebtoken::{decode, decode_header, jwk::JwkSet, DecodingKey, Validation};
+use reqwest::Url;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::{
+    env::var,
+    sync::{Arc, OnceLock},
+};
+
+/// An OpenID Connect identity provider users can sign in with, configured by
+/// `OIDC_ISSUER`, `OIDC_CLIENT_ID` and `OIDC_CLIENT_SECRET`.
+///
+/// Endpoints are discovered from the issuer on first use. Without a client
+/// secret the app acts as a public client and relies on PKCE alone.
+#[derive(Clone)]
+pub struct Provider {
+    pub issuer: String,
+    client_id: String,
+    client_secret: Option<String>,
+    redirect_uri: String,
+    scopes: String,
+    http: reqwest::Client,
+    metadata: Arc<OnceLock<Metadata>>,
+}
+
+#[derive(Deserialize)]
+struct Metadata {
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    id_token: String,
+}
+
+/// The ID token claims needed to sign a user in; signature, issuer, audience
+/// and expiry are checked while decoding.
+#[derive(Deserialize)]
+pub struct IdTokenClaims {
+    pub sub: String,
+    pub email: Option<String>,
+    #[serde(default)]
+    pub email_verified: bool,
+    nonce: Option<String>,
+}
+
+/// Returns the configured provider, or `None` when `OIDC_ISSUER` is unset.
+pub fn provider() -> Option<Provider> {
+    let issuer = var("OIDC_ISSUER").ok().filter(|issuer| !issuer.is_empty())?;
+    let client_id = var("OIDC_CLIENT_ID")
+        .ok()
+        .filter(|id| !id.is_empty())
+        .expect("OIDC_CLIENT_ID must be set when OIDC_ISSUER is");
+    let redirect_uri = var("OIDC_REDIRECT_URI").ok().filter(|uri| !uri.is_empty()).unwrap_or_else(|| {
+        let app_url = var("APP_URL").ok().filter(|url| !url.is_empty()).unwrap_or_else(|| "http://localhost:3000".to_string());
+        format!("{}/oidc/callback", app_url.trim_end_matches('/'))
+    });
+
+    Some(Provider {
+        issuer,
+        client_id,
+        client_secret: var("OIDC_CLIENT_SECRET").ok().filter(|secret| !secret.is_empty()),
+        redirect_uri,
+        scopes: var("OIDC_SCOPES").ok().filter(|scopes| !scopes.is_empty()).unwrap_or_else(|| "openid email profile".to_string()),
+        http: reqwest::Client::new(),
+        metadata: Arc::new(OnceLock::new()),
+    })
+}
+
+/// The S256 PKCE challenge for a code verifier.
+fn code_challenge(code_verifier: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(code_verifier.as_bytes()))
+}
+
+impl Provider {
+    async fn metadata(&self) -> Result<&Metadata, String> {
+        if let Some(metadata) = self.metadata.get() {
+            return Ok(metadata);
+        }
+
+        let metadata = self.http
+            .get(format!("{}/.well-known/openid-configuration", self.issuer.trim_end_matches('/')))
+            .send()
+            .await
+            .and_then(|resp| resp.error_for_status())
+            .map_err(|_| "Failed to reach identity provider".to_string())?
+            .json::<Metadata>()
+            .await
+            .map_err(|_| "Invalid identity provider configuration".to_string())?;
+        // Another request may have won the race; either copy is the same.
+        let _ = self.metadata.set(metadata);
+        Ok(self.metadata.get().unwrap())
+    }
+
+    pub async fn authorization_url(&self, state: &str, nonce: &str, code_verifier: &str) -> Result<String, String> {
+        let metadata = self.metadata().await?;
+        let mut url = Url::parse(&metadata.authorization_endpoint)
+            .map_err(|_| "Invalid identity provider configuration".to_string())?;
+        url.query_pairs_mut()
+            .append_pair("response_type", "code")
+            .append_pair("client_id", &self.client_id)
+            .append_pair("redirect_uri", &self.redirect_uri)
+            .append_pair("scope", &self.scopes)
+            .append_pair("state", state)
+            .append_pair("nonce", nonce)
+            .append_pair("code_challenge", &code_challenge(code_verifier))
+            .append_pair("code_challenge_method", "S256");
+        Ok(url.to_string())
+    }
+
+    /// Redeems an authorization code and returns the verified ID token claims.
+    pub async fn exchange(&self, code: &str, code_verifier: &str, nonce: &str) -> Result<IdTokenClaims, String> {
+        let metadata = self.metadata().await?;
+
+        let mut form = vec![
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", self.redirect_uri.as_str()),
+            ("code_verifier", code_verifier),
+        ];
+        let mut request = self.http.post(&metadata.token_endpoint);
+        match &self.client_secret {
+            Some(secret) => request = request.basic_auth(&self.client_id, Some(secret)),
+            None => form.push(("client_id", self.client_id.as_str())),
+        }
+        let tokens = request
+            .form(&form)
+            .send()
+            .await
+            .map_err(|_| "Failed to reach identity provider".to_string())?
+            .error_for_status()
+            .map_err(|_| "Identity provider rejected the sign-in".to_string())?
+            .json::<TokenResponse>()
+            .await
+            .map_err(|_| "Invalid response from identity provider".to_string())?;
+
+        let claims = self.verify_id_token(&metadata.jwks_uri, &tokens.id_token).await?;
+        if claims.nonce.as_deref() != Some(nonce) {
+            return Err("Invalid ID token".to_string());
+        }
+        Ok(claims)
+    }
+
+    async fn verify_id_token(&self, jwks_uri: &str, id_token: &str) -> Result<IdTokenClaims, String> {
+        let header = decode_header(id_token).map_err(|_| "Invalid ID token".to_string())?;
+        // Keys are fetched on every login so a rotation at the provider is
+        // picked up immediately; logins are rare enough for that not to matter.
+        let jwks = self.http
+            .get(jwks_uri)
+            .send()
+            .await
+            .and_then(|resp| resp.error_for_status())
+            .map_err(|_| "Failed to reach identity provider".to_string())?
+            .json::<JwkSet>()
+            .await
+            .map_err(|_| "Invalid identity provider keys".to_string())?;
+
+        let jwk = match &header.kid {
+            Some(kid) => jwks.find(kid),
+            None if jwks.keys.len() == 1 => jwks.keys.first(),
+            None => None,
+        }
+        .ok_or_else(|| "Invalid ID token".to_string())?;
+        let key = DecodingKey::from_jwk(jwk).map_err(|_| "Invalid identity provider keys".to_string())?;
+
+        let mut validation = Validation::new(header.alg);
+        validation.set_issuer(&[&self.issuer]);
+        validation.set_audience(&[&self.client_id]);
+        decode::<IdTokenClaims>(id_token, &key, &validation)
+            .map(|data| data.claims)
+            .map_err(|_| "Invalid ID token".to_string())
+    }
+}
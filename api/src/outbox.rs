@@ -0,0 +1,172 @@
+use sqlx::{PgPool, query};
+use std::{env::var, sync::Arc, time::Duration};
+use tokio::sync::Notify;
+use uuid::Uuid;
+
+use crate::mailer::{Email, MailError, Mailer};
+
+/// Messages claimed per round trip to the database.
+const BATCH_SIZE: i64 = 10;
+/// Seconds a claimed message is hidden from other workers while it is sent.
+/// A worker that dies mid-send leaves it to be retried after this.
+const LEASE_SECONDS: f64 = 300.0;
+/// Seconds between checks for due retries when nothing wakes the worker.
+const POLL_SECONDS: u64 = 5;
+/// Days sent and dead messages are kept for inspection.
+const RETENTION_DAYS: i32 = 7;
+
+/// Durable queue of outgoing email in `email_outbox`.
+///
+/// Requests only insert a row, so a slow or failing relay never holds up a
+/// response. A background worker delivers due messages, retrying failures
+/// with exponential backoff until `EMAIL_MAX_ATTEMPTS`, after which the
+/// message is marked dead and left for an operator to look at. Messages that
+/// are only useful for a while, like sign-in codes, are given an expiry and
+/// marked dead once it passes instead.
+#[derive(Clone)]
+pub struct Outbox {
+    pool: PgPool,
+    wake: Arc<Notify>,
+}
+
+/// Delivery attempts before a message is dead-lettered, `EMAIL_MAX_ATTEMPTS` (default 8).
+fn max_attempts() -> i32 {
+    var("EMAIL_MAX_ATTEMPTS").ok().and_then(|v| v.parse().ok()).unwrap_or(8)
+}
+
+/// Seconds to wait before the next attempt: 30s, 1m, 2m, ... capped at an hour.
+fn backoff_seconds(attempts: i32) -> f64 {
+    (30.0 * 2f64.powi(attempts.saturating_sub(1))).min(3600.0)
+}
+
+impl Outbox {
+    pub fn new(pool: PgPool) -> Outbox {
+        Outbox { pool, wake: Arc::new(Notify::new()) }
+    }
+
+    /// Queues an email and returns its id, which clients can poll at `/emails/{id}`.
+    /// With `expires_in_minutes`, the email is given up on if it can't be
+    /// delivered within that time, and its body isn't kept afterwards.
+    pub async fn enqueue(&self, user_id: Option<Uuid>, to: &str, email: Email, expires_in_minutes: Option<i32>) -> Result<Uuid, sqlx::Error> {
+        let queued = query!(
+            r#"
+            INSERT INTO email_outbox (user_id, recipient, subject, text_body, html_body, expires_at)
+            VALUES ($1, $2, $3, $4, $5, NOW() + make_interval(mins => $6))
+            RETURNING id
+            "#,
+            user_id,
+            to,
+            email.subject,
+            email.text,
+            email.html,
+            expires_in_minutes
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        self.wake.notify_one();
+        Ok(queued.id)
+    }
+
+    pub fn spawn_worker(&self, mailer: Mailer) {
+        let outbox = self.clone();
+        tokio::spawn(async move {
+            loop {
+                match outbox.deliver_due(&mailer).await {
+                    // A full batch means there may be more waiting.
+                    Ok(delivered) if delivered as i64 == BATCH_SIZE => continue,
+                    Ok(_) => {}
+                    Err(err) => eprintln!("Failed to process email outbox: {}", err),
+                }
+                let _ = tokio::time::timeout(Duration::from_secs(POLL_SECONDS), outbox.wake.notified()).await;
+            }
+        });
+    }
+
+    async fn deliver_due(&self, mailer: &Mailer) -> Result<usize, sqlx::Error> {
+        query!(
+            r#"
+            DELETE FROM email_outbox
+            WHERE status IN ('sent', 'dead') AND created_at < NOW() - make_interval(days => $1)
+            "#,
+            RETENTION_DAYS
+        )
+        .execute(&self.pool)
+        .await?;
+
+        query!(
+            r#"
+            UPDATE email_outbox SET status = 'dead', last_error = COALESCE(last_error, 'Expired before delivery'), text_body = '', html_body = ''
+            WHERE status = 'pending' AND expires_at <= NOW()
+            "#
+        )
+        .execute(&self.pool)
+        .await?;
+
+        let due = query!(
+            r#"
+            UPDATE email_outbox SET
+                attempts = attempts + 1,
+                next_attempt_at = NOW() + make_interval(secs => $2)
+            WHERE id IN (
+                SELECT id FROM email_outbox
+                WHERE status = 'pending' AND next_attempt_at <= NOW() AND (expires_at IS NULL OR expires_at > NOW())
+                ORDER BY next_attempt_at
+                LIMIT $1
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING id, recipient, subject, text_body, html_body, attempts
+            "#,
+            BATCH_SIZE,
+            LEASE_SECONDS
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let count = due.len();
+        for message in due {
+            let email = Email { subject: message.subject, text: message.text_body, html: message.html_body };
+            match mailer.send(&message.recipient, email).await {
+                // Bodies can hold sign-in codes and links, so they aren't kept once delivered.
+                Ok(()) => query!(
+                    r#"
+                    UPDATE email_outbox SET status = 'sent', sent_at = NOW(), last_error = NULL, text_body = '', html_body = ''
+                    WHERE id = $1
+                    "#,
+                    message.id
+                )
+                .execute(&self.pool)
+                .await?,
+                Err(err) => {
+                    eprintln!("{} (attempt {} for email {})", err, message.attempts, message.id);
+                    let retry = !matches!(err, MailError::InvalidAddress(_)) && message.attempts < max_attempts();
+                    // Not retried if it would expire before the next attempt,
+                    // and, like sent ones, expiring messages lose their body
+                    // once dead.
+                    query!(
+                        r#"
+                        UPDATE email_outbox SET
+                            status = CASE WHEN next.retry THEN 'pending' ELSE 'dead' END,
+                            last_error = $2,
+                            next_attempt_at = NOW() + make_interval(secs => $4),
+                            text_body = CASE WHEN next.retry OR expires_at IS NULL THEN text_body ELSE '' END,
+                            html_body = CASE WHEN next.retry OR expires_at IS NULL THEN html_body ELSE '' END
+                        FROM (
+                            SELECT $3 AND (expires_at IS NULL OR expires_at > NOW() + make_interval(secs => $4)) AS retry
+                            FROM email_outbox WHERE id = $1
+                        ) AS next
+                        WHERE id = $1
+                        "#,
+                        message.id,
+                        err.to_string(),
+                        retry,
+                        backoff_seconds(message.attempts)
+                    )
+                    .execute(&self.pool)
+                    .await?
+                }
+            };
+        }
+        Ok(count)
+    }
+}
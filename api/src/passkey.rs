@@ -0,0 +1,103 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use serde::{de::DeserializeOwned, Serialize};
+use sqlx::{PgPool, query};
+use std::env::var;
+use uuid::Uuid;
+use webauthn_rs::prelude::{CredentialID, Passkey, Url, Webauthn, WebauthnBuilder};
+
+/// Minutes a started registration or login ceremony can be finished in.
+const CEREMONY_TTL_MINUTES: i32 = 5;
+
+/// Builds the relying party from `WEBAUTHN_RP_ID`, `WEBAUTHN_RP_ORIGIN` and
+/// `WEBAUTHN_RP_NAME`, defaulting to the local web app.
+pub fn webauthn() -> Webauthn {
+    let rp_origin = var("WEBAUTHN_RP_ORIGIN")
+        .ok()
+        .filter(|origin| !origin.is_empty())
+        .or_else(|| var("APP_URL").ok().filter(|url| !url.is_empty()))
+        .unwrap_or_else(|| "http://localhost:3000".to_string());
+    let rp_origin = Url::parse(&rp_origin).expect("WEBAUTHN_RP_ORIGIN must be a valid URL");
+    let rp_id = var("WEBAUTHN_RP_ID")
+        .ok()
+        .filter(|id| !id.is_empty())
+        .or_else(|| rp_origin.host_str().map(|host| host.to_string()))
+        .expect("WEBAUTHN_RP_ID must be set");
+    let rp_name = var("WEBAUTHN_RP_NAME").ok().filter(|name| !name.is_empty()).unwrap_or_else(|| "Notes".to_string());
+
+    WebauthnBuilder::new(&rp_id, &rp_origin)
+        .expect("Invalid WebAuthn configuration")
+        .rp_name(&rp_name)
+        .build()
+        .expect("Invalid WebAuthn configuration")
+}
+
+/// Persists the server half of a ceremony until the client finishes it.
+pub async fn save_ceremony<T: Serialize>(pool: &PgPool, user_id: Uuid, kind: &str, state: &T) -> Result<Uuid, sqlx::Error> {
+    query!(
+        r#"
+        DELETE FROM webauthn_ceremonies WHERE expires_at < NOW()
+        "#
+    )
+    .execute(pool)
+    .await?;
+
+    let ceremony = query!(
+        r#"
+        INSERT INTO webauthn_ceremonies (user_id, kind, state, expires_at)
+        VALUES ($1, $2, $3, NOW() + make_interval(mins => $4))
+        RETURNING id
+        "#,
+        user_id,
+        kind,
+        serde_json::to_value(state).expect("Failed to serialize ceremony state"),
+        CEREMONY_TTL_MINUTES
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(ceremony.id)
+}
+
+/// Removes and returns a pending ceremony, so each challenge is answered at most once.
+pub async fn take_ceremony<T: DeserializeOwned>(pool: &PgPool, ceremony_id: Uuid, kind: &str) -> Result<Option<(Uuid, T)>, sqlx::Error> {
+    let ceremony = query!(
+        r#"
+        DELETE FROM webauthn_ceremonies
+        WHERE id = $1 AND kind = $2
+        RETURNING user_id, state, expires_at > NOW() AS "valid!"
+        "#,
+        ceremony_id,
+        kind
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(ceremony
+        .filter(|ceremony| ceremony.valid)
+        .and_then(|ceremony| {
+            serde_json::from_value(ceremony.state)
+                .ok()
+                .map(|state| (ceremony.user_id, state))
+        }))
+}
+
+/// Loads a user's stored passkeys, keyed by their row id.
+pub async fn user_passkeys(pool: &PgPool, user_id: Uuid) -> Result<Vec<(Uuid, Passkey)>, sqlx::Error> {
+    let passkeys = query!(
+        r#"
+        SELECT id, passkey FROM passkeys WHERE user_id = $1
+        "#,
+        user_id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(passkeys
+        .into_iter()
+        .filter_map(|row| serde_json::from_value(row.passkey).ok().map(|passkey| (row.id, passkey)))
+        .collect())
+}
+
+pub fn credential_id(id: &CredentialID) -> String {
+    URL_SAFE_NO_PAD.encode(id.as_ref())
+}
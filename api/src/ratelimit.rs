@@ -0,0 +1,213 @@
+use axum::{
+    body::{to_bytes, Body},
+    extract::{Request, State},
+    http::{header::RETRY_AFTER, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::{json, Value};
+use sqlx::{PgPool, query};
+use std::{
+    collections::HashMap,
+    env::var,
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+use crate::session::ClientInfo;
+
+/// Largest request body read to find the email address.
+const MAX_BODY_BYTES: usize = 64 * 1024;
+/// Buckets kept in memory before full ones are dropped.
+const MAX_MEMORY_BUCKETS: usize = 100_000;
+
+/// A token bucket holding up to `capacity` requests, refilled evenly over
+/// `period_seconds`.
+#[derive(Clone, Copy)]
+struct Limit {
+    capacity: f64,
+    period_seconds: f64,
+}
+
+impl Limit {
+    /// Parses `<requests>/<period>` such as `5/15m`, with the period in
+    /// seconds (`s`), minutes (`m`), hours (`h`) or days (`d`).
+    fn parse(spec: &str) -> Option<Limit> {
+        let (capacity, period) = spec.trim().split_once('/')?;
+        let capacity: f64 = capacity.trim().parse().ok().filter(|capacity: &f64| *capacity >= 1.0)?;
+        let period = period.trim();
+        let (amount, unit) = period.split_at(period.find(|c: char| !c.is_ascii_digit()).unwrap_or(period.len()));
+        let amount: f64 = if amount.is_empty() { 1.0 } else { amount.parse().ok()? };
+        let unit_seconds = match unit {
+            "s" => 1.0,
+            "m" => 60.0,
+            "h" => 3600.0,
+            "d" => 86400.0,
+            _ => return None,
+        };
+        Some(Limit { capacity, period_seconds: amount * unit_seconds }).filter(|limit| limit.period_seconds > 0.0)
+    }
+
+    fn from_env(key: &str, default: &str) -> Limit {
+        let spec = var(key).unwrap_or_else(|_| default.to_string());
+        Limit::parse(&spec).unwrap_or_else(|| panic!("{} must look like 5/15m", key))
+    }
+
+    /// Refills a bucket last seen `elapsed` seconds ago and takes a token,
+    /// returning the new level and, when empty, the seconds until one is free.
+    fn take(&self, tokens: f64, elapsed: f64) -> (f64, Option<u64>) {
+        let refill_per_second = self.capacity / self.period_seconds;
+        let tokens = (tokens + elapsed.max(0.0) * refill_per_second).min(self.capacity);
+        if tokens >= 1.0 {
+            (tokens - 1.0, None)
+        } else {
+            (tokens, Some(((1.0 - tokens) / refill_per_second).ceil() as u64))
+        }
+    }
+}
+
+/// Tokens left, when they were counted and the limit they are counted against.
+type MemoryBuckets = HashMap<String, (f64, Instant, Limit)>;
+
+#[derive(Clone)]
+enum Store {
+    Memory(Arc<Mutex<MemoryBuckets>>),
+    Postgres(PgPool),
+}
+
+/// Per-email and per-IP token buckets for the sign-in endpoints, configured by
+/// `RATE_LIMIT_PER_EMAIL` and `RATE_LIMIT_PER_IP`.
+///
+/// Buckets live in memory unless `RATE_LIMIT_STORE=postgres`, which shares
+/// them between instances. If the store fails, requests are let through
+/// rather than locking everyone out.
+#[derive(Clone)]
+pub struct RateLimiter {
+    store: Store,
+    per_email: Limit,
+    per_ip: Limit,
+}
+
+pub fn limiter(pool: &PgPool) -> RateLimiter {
+    let store = match var("RATE_LIMIT_STORE").ok().filter(|store| !store.is_empty()).as_deref() {
+        None | Some("memory") => Store::Memory(Arc::new(Mutex::new(HashMap::new()))),
+        Some("postgres") => Store::Postgres(pool.clone()),
+        Some(other) => panic!("Unknown RATE_LIMIT_STORE {}, expected memory or postgres", other),
+    };
+
+    RateLimiter {
+        store,
+        per_email: Limit::from_env("RATE_LIMIT_PER_EMAIL", "5/15m"),
+        per_ip: Limit::from_env("RATE_LIMIT_PER_IP", "30/15m"),
+    }
+}
+
+impl RateLimiter {
+    async fn take(&self, key: String, limit: Limit) -> Result<Option<u64>, sqlx::Error> {
+        match &self.store {
+            Store::Memory(buckets) => {
+                let mut buckets = buckets.lock().unwrap();
+                if buckets.len() >= MAX_MEMORY_BUCKETS {
+                    // Buckets that have refilled behave like new ones, so they can go.
+                    let now = Instant::now();
+                    buckets.retain(|_, (tokens, updated, limit)| {
+                        limit.take(*tokens, now.duration_since(*updated).as_secs_f64()).0 + 1.0 < limit.capacity
+                    });
+                }
+                let now = Instant::now();
+                let (tokens, updated, _) = buckets.get(&key).copied().unwrap_or((limit.capacity, now, limit));
+                let (tokens, retry_after) = limit.take(tokens, now.duration_since(updated).as_secs_f64());
+                buckets.insert(key, (tokens, now, limit));
+                Ok(retry_after)
+            }
+            Store::Postgres(pool) => {
+                let mut tx = pool.begin().await?;
+                query!(
+                    r#"
+                    INSERT INTO rate_limit_buckets (key, tokens) VALUES ($1, $2)
+                    ON CONFLICT (key) DO NOTHING
+                    "#,
+                    key,
+                    limit.capacity
+                )
+                .execute(&mut *tx)
+                .await?;
+                let bucket = query!(
+                    r#"
+                    SELECT tokens, EXTRACT(EPOCH FROM NOW() - updated_at)::float8 AS "elapsed!"
+                    FROM rate_limit_buckets WHERE key = $1
+                    FOR UPDATE
+                    "#,
+                    key
+                )
+                .fetch_one(&mut *tx)
+                .await?;
+
+                let (tokens, retry_after) = limit.take(bucket.tokens, bucket.elapsed);
+                query!(
+                    r#"
+                    UPDATE rate_limit_buckets SET tokens = $2, updated_at = NOW() WHERE key = $1
+                    "#,
+                    key,
+                    tokens
+                )
+                .execute(&mut *tx)
+                .await?;
+                tx.commit().await?;
+
+                // Every bucket is full again after a day.
+                if rand::random_ratio(1, 100) {
+                    query!(
+                        r#"
+                        DELETE FROM rate_limit_buckets WHERE updated_at < NOW() - INTERVAL '1 day'
+                        "#
+                    )
+                    .execute(pool)
+                    .await?;
+                }
+                Ok(retry_after)
+            }
+        }
+    }
+}
+
+/// Middleware for routes taking an `email` in their JSON body. Each request
+/// costs a token from both the email's and the client IP's bucket for that
+/// route.
+pub async fn limit(State(limiter): State<RateLimiter>, client: ClientInfo, request: Request, next: Next) -> Response {
+    let (parts, body) = request.into_parts();
+    let Ok(bytes) = to_bytes(body, MAX_BODY_BYTES).await else {
+        return StatusCode::PAYLOAD_TOO_LARGE.into_response();
+    };
+    let email = serde_json::from_slice::<Value>(&bytes)
+        .ok()
+        .and_then(|body| body.get("email").and_then(|email| email.as_str()).map(|email| email.trim().to_lowercase()));
+
+    let path = parts.uri.path();
+    let mut keys = Vec::new();
+    if let Some(email) = email {
+        keys.push((format!("{}:email:{}", path, email), limiter.per_email));
+    }
+    if let Some(ip) = &client.ip_address {
+        keys.push((format!("{}:ip:{}", path, ip), limiter.per_ip));
+    }
+
+    let mut retry_after = None;
+    for (key, limit) in keys {
+        match limiter.take(key, limit).await {
+            Ok(wait) => retry_after = retry_after.max(wait),
+            Err(err) => eprintln!("Rate limiter unavailable: {}", err),
+        }
+    }
+
+    if let Some(retry_after) = retry_after {
+        return (
+            StatusCode::TOO_MANY_REQUESTS,
+            [(RETRY_AFTER, retry_after.to_string())],
+            Json(json!({ "error": "Too many requests, please try again later" })),
+        ).into_response();
+    }
+
+    next.run(Request::from_parts(parts, Body::from(bytes))).await
+}
@@ -0,0 +1,156 @@
+use sqlx::{PgPool, query};
+use std::collections::HashSet;
+use uuid::Uuid;
+
+/// Characters a chunk grows to before a new one is started.
+const CHUNK_CHARS: usize = 800;
+/// Documents whose chunks are scored when searching across the notebook.
+const CANDIDATE_DOCS: i64 = 8;
+/// Recently edited documents used when nothing matches the question.
+const FALLBACK_DOCS: i64 = 3;
+/// Passages handed to the model at most.
+const MAX_PASSAGES: usize = 8;
+/// Characters of passages handed to the model at most.
+const MAX_PASSAGE_CHARS: usize = 6000;
+
+/// An excerpt of one of the user's documents.
+pub struct Passage {
+    pub doc_id: Uuid,
+    pub title: String,
+    pub text: String,
+}
+
+/// Splits text into chunks of about `CHUNK_CHARS`, keeping paragraphs
+/// together where they fit and breaking long ones between words.
+pub fn chunks(text: &str) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+    for paragraph in text.split('\n').map(str::trim).filter(|paragraph| !paragraph.is_empty()) {
+        for piece in split_long(paragraph) {
+            if !current.is_empty() && current.chars().count() + piece.chars().count() > CHUNK_CHARS {
+                chunks.push(std::mem::take(&mut current));
+            }
+            if !current.is_empty() {
+                current.push('\n');
+            }
+            current.push_str(&piece);
+        }
+    }
+    if !current.is_empty() {
+        chunks.push(current);
+    }
+    chunks
+}
+
+fn split_long(paragraph: &str) -> Vec<String> {
+    let mut pieces = Vec::new();
+    let mut current = String::new();
+    for word in paragraph.split_whitespace() {
+        if !current.is_empty() && current.chars().count() + word.chars().count() >= CHUNK_CHARS {
+            pieces.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            current.push(' ');
+        }
+        current.push_str(word);
+    }
+    if !current.is_empty() {
+        pieces.push(current);
+    }
+    pieces
+}
+
+/// Lowercased words of three or more characters, used to score chunks.
+fn terms(text: &str) -> HashSet<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| word.chars().count() >= 3)
+        .map(str::to_lowercase)
+        .collect()
+}
+
+fn score(chunk: &str, terms: &HashSet<String>) -> f64 {
+    let chunk = chunk.to_lowercase();
+    terms.iter().map(|term| (1.0 + chunk.matches(term.as_str()).count() as f64).ln()).sum()
+}
+
+/// Finds the passages most relevant to `question`, from one document when
+/// `doc_id` is given or from all of the user's documents otherwise.
+pub async fn retrieve(pool: &PgPool, user_id: Uuid, doc_id: Option<Uuid>, question: &str) -> Result<Vec<Passage>, sqlx::Error> {
+    let docs = match doc_id {
+        Some(doc_id) => query!(
+            r#"
+            SELECT id, title, content_text FROM docs WHERE id = $1 AND user_id = $2
+            "#,
+            doc_id,
+            user_id
+        )
+        .fetch_all(pool)
+        .await?
+        .into_iter()
+        .map(|doc| (doc.id, doc.title, doc.content_text))
+        .collect(),
+        None => candidates(pool, user_id, question).await?,
+    };
+
+    let terms = terms(question);
+    let mut scored: Vec<(f64, usize, Passage)> = Vec::new();
+    for (doc_id, title, content_text) in docs {
+        for text in chunks(&content_text) {
+            let order = scored.len();
+            scored.push((score(&text, &terms), order, Passage { doc_id, title: title.clone(), text }));
+        }
+    }
+    // Best matches first; ties keep document order, so a short document is
+    // read front to back.
+    scored.sort_by(|a, b| b.0.total_cmp(&a.0).then(a.1.cmp(&b.1)));
+
+    let mut passages = Vec::new();
+    let mut remaining = MAX_PASSAGE_CHARS;
+    for (_, _, passage) in scored {
+        let len = passage.text.chars().count();
+        if passages.len() == MAX_PASSAGES || len > remaining {
+            break;
+        }
+        remaining -= len;
+        passages.push(passage);
+    }
+    Ok(passages)
+}
+
+/// Documents matching any word of the question, best first, or the most
+/// recently edited ones when none do.
+async fn candidates(pool: &PgPool, user_id: Uuid, question: &str) -> Result<Vec<(Uuid, String, String)>, sqlx::Error> {
+    // plainto_tsquery ANDs the words together, which is too strict for a
+    // question, so its terms are ORed instead.
+    let docs = query!(
+        r#"
+        WITH q AS (
+            SELECT replace(plainto_tsquery('english', $2)::text, ' & ', ' | ')::tsquery AS query
+        )
+        SELECT id, title, content_text FROM docs, q
+        WHERE user_id = $1 AND numnode(q.query) > 0
+            AND search @@ q.query
+        ORDER BY ts_rank(search, q.query) DESC
+        LIMIT $3
+        "#,
+        user_id,
+        question,
+        CANDIDATE_DOCS
+    )
+    .fetch_all(pool)
+    .await?;
+    if !docs.is_empty() {
+        return Ok(docs.into_iter().map(|doc| (doc.id, doc.title, doc.content_text)).collect());
+    }
+
+    let docs = query!(
+        r#"
+        SELECT id, title, content_text FROM docs WHERE user_id = $1 ORDER BY updated_at DESC LIMIT $2
+        "#,
+        user_id,
+        FALLBACK_DOCS
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(docs.into_iter().map(|doc| (doc.id, doc.title, doc.content_text)).collect())
+}
@@ -0,0 +1,65 @@
+use sqlx::{PgConnection, query};
+use uuid::Uuid;
+
+/// Seconds after a revision is started during which further saves by the
+/// same author are folded into it, so autosaving while typing doesn't leave
+/// a revision behind every pause.
+const COALESCE_SECONDS: f64 = 300.0;
+
+/// Records the current content of a document as a revision by `author_id`.
+///
+/// With `coalesce`, the latest revision is updated instead when it is the
+/// same author's and was started less than `COALESCE_SECONDS` ago. A save
+/// that changed nothing since the latest revision records nothing.
+pub async fn record(conn: &mut PgConnection, doc_id: Uuid, author_id: Uuid, coalesce: bool) -> Result<(), sqlx::Error> {
+    let latest = query!(
+        r#"
+        SELECT doc_revisions.id, doc_revisions.author_id,
+            doc_revisions.created_at > NOW() - make_interval(secs => $2) AS "recent!",
+            (doc_revisions.title, doc_revisions.content_text, doc_revisions.content_json, doc_revisions.content_html)
+                IS NOT DISTINCT FROM (docs.title, docs.content_text, docs.content_json, docs.content_html) AS "unchanged!"
+        FROM doc_revisions JOIN docs ON docs.id = doc_revisions.doc_id
+        WHERE doc_revisions.doc_id = $1
+        ORDER BY doc_revisions.created_at DESC
+        LIMIT 1
+        "#,
+        doc_id,
+        COALESCE_SECONDS
+    )
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    if let Some(latest) = latest {
+        if latest.unchanged {
+            return Ok(());
+        }
+        if coalesce && latest.recent && latest.author_id == Some(author_id) {
+            query!(
+                r#"
+                UPDATE doc_revisions
+                SET (title, content_text, content_json, content_html) = (
+                    SELECT title, content_text, content_json, content_html FROM docs WHERE id = $2
+                ), updated_at = NOW()
+                WHERE id = $1
+                "#,
+                latest.id,
+                doc_id
+            )
+            .execute(&mut *conn)
+            .await?;
+            return Ok(());
+        }
+    }
+
+    query!(
+        r#"
+        INSERT INTO doc_revisions (doc_id, author_id, title, content_text, content_json, content_html)
+        SELECT id, $2, title, content_text, content_json, content_html FROM docs WHERE id = $1
+        "#,
+        doc_id,
+        author_id
+    )
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,89 @@
+use axum::{
+    Json,
+    Extension, http::StatusCode,
+    extract::Path,
+};
+use sqlx::{PgPool, query};
+use uuid::Uuid;
+
+use crate::actions::Action;
+use crate::ai::Provider;
+use crate::auth::CurrentUser;
+use crate::routes::aiactions::{ActionRequest, ActionResponse};
+
+pub async fn put_handler(
+    Path(action_id): Path<String>,
+    Extension(pool): Extension<PgPool>,
+    Extension(provider): Extension<Provider>,
+    Extension(auth_user): Extension<CurrentUser>,
+    Json(payload): Json<ActionRequest>
+) -> (StatusCode, Json<ActionResponse>) {
+    let Ok(action_id) = Uuid::parse_str(&action_id) else {
+        return (StatusCode::NOT_FOUND, Json(ActionResponse { action: None, error: Some("Action not found".to_string()) }));
+    };
+    if let Err(error) = payload.validate(&provider) {
+        return (StatusCode::BAD_REQUEST, Json(ActionResponse { action: None, error: Some(error.to_string()) }));
+    }
+
+    let action = query!(
+        r#"
+        UPDATE ai_actions SET
+            label = $3,
+            system_prompt = $4,
+            temperature = $5,
+            model = $6,
+            shared = COALESCE($7, shared),
+            updated_at = NOW()
+        WHERE id = $1 AND user_id = $2
+        RETURNING id, label, system_prompt, temperature, model, shared
+        "#,
+        action_id,
+        Uuid::parse_str(&auth_user.id).unwrap(),
+        payload.label.trim(),
+        payload.system_prompt.trim(),
+        payload.temperature,
+        payload.model(),
+        payload.shared
+    )
+    .fetch_optional(&pool)
+    .await
+    .expect("Failed to update AI action");
+
+    match action {
+        Some(action) => {
+            let action = Action::custom(action.id, action.label, action.system_prompt, action.temperature, action.model, action.shared, true);
+            (StatusCode::OK, Json(ActionResponse { action: Some(action), error: None }))
+        }
+        None => (StatusCode::NOT_FOUND, Json(ActionResponse { action: None, error: Some("Action not found".to_string()) })),
+    }
+}
+
+pub async fn delete_handler(
+    Path(action_id): Path<String>,
+    Extension(pool): Extension<PgPool>,
+    Extension(auth_user): Extension<CurrentUser>
+) -> (StatusCode, Json<ActionResponse>) {
+    let Ok(action_id) = Uuid::parse_str(&action_id) else {
+        return (StatusCode::NOT_FOUND, Json(ActionResponse { action: None, error: Some("Action not found".to_string()) }));
+    };
+
+    let action = query!(
+        r#"
+        DELETE FROM ai_actions WHERE id = $1 AND user_id = $2
+        RETURNING id, label, system_prompt, temperature, model, shared
+        "#,
+        action_id,
+        Uuid::parse_str(&auth_user.id).unwrap()
+    )
+    .fetch_optional(&pool)
+    .await
+    .expect("Failed to delete AI action");
+
+    match action {
+        Some(action) => {
+            let action = Action::custom(action.id, action.label, action.system_prompt, action.temperature, action.model, action.shared, true);
+            (StatusCode::OK, Json(ActionResponse { action: Some(action), error: None }))
+        }
+        None => (StatusCode::NOT_FOUND, Json(ActionResponse { action: None, error: Some("Action not found".to_string()) })),
+    }
+}
@@ -0,0 +1,96 @@
+use axum::{
+    Json,
+    Extension, http::StatusCode,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, query};
+use uuid::Uuid;
+
+use crate::actions::{self, Action};
+use crate::ai::Provider;
+use crate::auth::CurrentUser;
+
+pub async fn get_handler(Extension(pool): Extension<PgPool>, Extension(auth_user): Extension<CurrentUser>) -> (StatusCode, Json<ActionsResponse>) {
+    let actions = actions::list(&pool, Uuid::parse_str(&auth_user.id).unwrap())
+        .await
+        .expect("Failed to fetch AI actions");
+
+    (StatusCode::OK, Json(ActionsResponse { actions, error: None }))
+}
+
+/// Creates a custom action. Shared actions show up for every user, but only
+/// their creator can change them.
+pub async fn post_handler(
+    Extension(pool): Extension<PgPool>,
+    Extension(provider): Extension<Provider>,
+    Extension(auth_user): Extension<CurrentUser>,
+    Json(payload): Json<ActionRequest>
+) -> (StatusCode, Json<ActionResponse>) {
+    if let Err(error) = payload.validate(&provider) {
+        return (StatusCode::BAD_REQUEST, Json(ActionResponse { action: None, error: Some(error.to_string()) }));
+    }
+
+    let action = query!(
+        r#"
+        INSERT INTO ai_actions (user_id, label, system_prompt, temperature, model, shared)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        RETURNING id, label, system_prompt, temperature, model, shared
+        "#,
+        Uuid::parse_str(&auth_user.id).unwrap(),
+        payload.label.trim(),
+        payload.system_prompt.trim(),
+        payload.temperature,
+        payload.model(),
+        payload.shared.unwrap_or(false)
+    )
+    .fetch_one(&pool)
+    .await
+    .expect("Failed to create AI action");
+
+    let action = Action::custom(action.id, action.label, action.system_prompt, action.temperature, action.model, action.shared, true);
+    (StatusCode::CREATED, Json(ActionResponse { action: Some(action), error: None }))
+}
+
+#[derive(Deserialize)]
+pub struct ActionRequest {
+    pub label: String,
+    pub system_prompt: String,
+    pub temperature: Option<f32>,
+    pub model: Option<String>,
+    pub shared: Option<bool>,
+}
+
+impl ActionRequest {
+    pub fn validate(&self, provider: &Provider) -> Result<(), &'static str> {
+        if self.label.trim().is_empty() || self.label.trim().chars().count() > 100 {
+            return Err("Label must be between 1 and 100 characters");
+        }
+        if self.system_prompt.trim().is_empty() || self.system_prompt.chars().count() > 4000 {
+            return Err("System prompt must be between 1 and 4000 characters");
+        }
+        if self.temperature.is_some_and(|temperature| !(0.0..=2.0).contains(&temperature)) {
+            return Err("Temperature must be between 0 and 2");
+        }
+        if self.model().is_some_and(|model| !provider.allows_model(model)) {
+            return Err("Model is not available");
+        }
+        Ok(())
+    }
+
+    /// The model to run the action with, `None` for the configured default.
+    pub fn model(&self) -> Option<&str> {
+        self.model.as_deref().map(str::trim).filter(|model| !model.is_empty())
+    }
+}
+
+#[derive(Serialize)]
+pub struct ActionsResponse {
+    actions: Vec<Action>,
+    error: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct ActionResponse {
+    pub action: Option<Action>,
+    pub error: Option<String>,
+}
@@ -0,0 +1,107 @@
+use axum::{
+    Json,
+    Extension, http::StatusCode,
+    extract::Path,
+};
+use serde::Serialize;
+use serde_json::Value;
+use sqlx::{PgPool, query};
+use uuid::Uuid;
+
+use crate::auth::CurrentUser;
+use crate::routes::chats::Thread;
+
+pub async fn get_handler(
+    Path(thread_id): Path<String>,
+    Extension(pool): Extension<PgPool>,
+    Extension(auth_user): Extension<CurrentUser>
+) -> (StatusCode, Json<ChatResponse>) {
+    let Ok(thread_id) = Uuid::parse_str(&thread_id) else {
+        return (StatusCode::NOT_FOUND, Json(ChatResponse { thread: None, messages: Vec::new(), error: Some("Chat not found".to_string()) }));
+    };
+
+    let thread = query!(
+        r#"
+        SELECT id, doc_id, title, created_at, updated_at FROM chat_threads WHERE id = $1 AND user_id = $2
+        "#,
+        thread_id,
+        Uuid::parse_str(&auth_user.id).unwrap()
+    )
+    .fetch_optional(&pool)
+    .await
+    .expect("Failed to fetch chat");
+    let Some(thread) = thread else {
+        return (StatusCode::NOT_FOUND, Json(ChatResponse { thread: None, messages: Vec::new(), error: Some("Chat not found".to_string()) }));
+    };
+
+    let messages = query!(
+        r#"
+        SELECT id, role, content, citations, created_at FROM chat_messages WHERE thread_id = $1 ORDER BY created_at
+        "#,
+        thread_id
+    )
+    .fetch_all(&pool)
+    .await
+    .expect("Failed to fetch chat messages");
+
+    (StatusCode::OK, Json(ChatResponse {
+        thread: Some(Thread {
+            id: thread.id.to_string(),
+            doc_id: thread.doc_id.map(|id| id.to_string()),
+            title: thread.title,
+            created_at: thread.created_at.to_string(),
+            updated_at: thread.updated_at.to_string(),
+        }),
+        messages: messages.into_iter().map(|message| ChatMessage {
+            id: message.id.to_string(),
+            role: message.role,
+            content: message.content,
+            citations: message.citations,
+            created_at: message.created_at.to_string(),
+        }).collect(),
+        error: None,
+    }))
+}
+
+pub async fn delete_handler(
+    Path(thread_id): Path<String>,
+    Extension(pool): Extension<PgPool>,
+    Extension(auth_user): Extension<CurrentUser>
+) -> (StatusCode, Json<ChatResponse>) {
+    let Ok(thread_id) = Uuid::parse_str(&thread_id) else {
+        return (StatusCode::NOT_FOUND, Json(ChatResponse { thread: None, messages: Vec::new(), error: Some("Chat not found".to_string()) }));
+    };
+
+    let deleted = query!(
+        r#"
+        DELETE FROM chat_threads WHERE id = $1 AND user_id = $2
+        "#,
+        thread_id,
+        Uuid::parse_str(&auth_user.id).unwrap()
+    )
+    .execute(&pool)
+    .await
+    .expect("Failed to delete chat");
+
+    if deleted.rows_affected() == 0 {
+        return (StatusCode::NOT_FOUND, Json(ChatResponse { thread: None, messages: Vec::new(), error: Some("Chat not found".to_string()) }));
+    }
+    (StatusCode::OK, Json(ChatResponse { thread: None, messages: Vec::new(), error: None }))
+}
+
+#[derive(Serialize)]
+pub struct ChatResponse {
+    thread: Option<Thread>,
+    messages: Vec<ChatMessage>,
+    error: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct ChatMessage {
+    pub id: String,
+    pub role: String,
+    pub content: String,
+    /// `[{index, doc_id, title, excerpt}]` for the passages an answer cites.
+    pub citations: Value,
+    pub created_at: String,
+}
@@ -0,0 +1,210 @@
+use axum::{
+    Json,
+    Extension, http::StatusCode,
+    extract::Path,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::{PgPool, query};
+use uuid::Uuid;
+
+use crate::ai::{Message, Provider};
+use crate::auth::CurrentUser;
+use crate::retrieval::{self, Passage};
+use crate::routes::chatdetails::ChatMessage;
+use crate::usage;
+
+/// Earlier messages of the thread sent along with a question.
+const HISTORY_MESSAGES: i64 = 10;
+/// Longest question accepted.
+const MAX_QUESTION_CHARS: usize = 4000;
+/// Characters of a cited passage returned with the answer.
+const EXCERPT_CHARS: usize = 200;
+
+/// Answers a question in a chat thread from the passages of the user's
+/// documents that best match it, citing the ones the answer relies on.
+/// Both the question and the answer are added to the thread.
+pub async fn post_handler(
+    Path(thread_id): Path<String>,
+    Extension(pool): Extension<PgPool>,
+    Extension(provider): Extension<Provider>,
+    Extension(auth_user): Extension<CurrentUser>,
+    Json(payload): Json<ChatMessageRequest>
+) -> (StatusCode, Json<ChatMessageResponse>) {
+    let user_id = Uuid::parse_str(&auth_user.id).unwrap();
+    let Ok(thread_id) = Uuid::parse_str(&thread_id) else {
+        return (StatusCode::NOT_FOUND, Json(ChatMessageResponse { message: None, error: Some("Chat not found".to_string()) }));
+    };
+    let question = payload.content.trim();
+    if question.is_empty() || question.chars().count() > MAX_QUESTION_CHARS {
+        return (StatusCode::BAD_REQUEST, Json(ChatMessageResponse { message: None, error: Some(format!("Message must be between 1 and {} characters", MAX_QUESTION_CHARS)) }));
+    }
+
+    let thread = query!(
+        r#"
+        SELECT chat_threads.doc_id, docs.title AS "doc_title?" FROM chat_threads
+        LEFT JOIN docs ON docs.id = chat_threads.doc_id
+        WHERE chat_threads.id = $1 AND chat_threads.user_id = $2
+        "#,
+        thread_id,
+        user_id
+    )
+    .fetch_optional(&pool)
+    .await
+    .expect("Failed to fetch chat");
+    let Some(thread) = thread else {
+        return (StatusCode::NOT_FOUND, Json(ChatMessageResponse { message: None, error: Some("Chat not found".to_string()) }));
+    };
+
+    let mut history = query!(
+        r#"
+        SELECT role, content FROM chat_messages WHERE thread_id = $1 ORDER BY created_at DESC LIMIT $2
+        "#,
+        thread_id,
+        HISTORY_MESSAGES
+    )
+    .fetch_all(&pool)
+    .await
+    .expect("Failed to fetch chat messages");
+    history.reverse();
+
+    // Follow-ups like "what about the second one?" need the previous question
+    // to find anything.
+    let previous_question = history.iter().rev().find(|message| message.role == "user").map(|message| message.content.as_str());
+    let search = match previous_question {
+        Some(previous) => format!("{}\n{}", previous, question),
+        None => question.to_string(),
+    };
+    let passages = retrieval::retrieve(&pool, user_id, thread.doc_id, &search)
+        .await
+        .expect("Failed to retrieve passages");
+
+    let mut messages = vec![Message::system(system_prompt(thread.doc_title.as_deref(), &passages))];
+    messages.extend(history.into_iter().map(|message| match message.role.as_str() {
+        "assistant" => Message::assistant(message.content),
+        _ => Message::user(message.content),
+    }));
+    messages.push(Message::user(question.to_string()));
+
+    let usage_id = match usage::reserve(&pool, user_id, provider.model()).await.expect("Failed to check AI usage") {
+        Ok(usage_id) => usage_id,
+        Err(error) => return (StatusCode::TOO_MANY_REQUESTS, Json(ChatMessageResponse { message: None, error: Some(error) })),
+    };
+    let completion = match provider.complete(provider.chat(messages)).await {
+        Ok(completion) => completion,
+        Err(err) => return (err.status(), Json(ChatMessageResponse { message: None, error: Some(err.to_string()) })),
+    };
+    if let Some(tokens) = completion.usage {
+        usage::record(&pool, usage_id, tokens.prompt_tokens, tokens.completion_tokens)
+            .await
+            .expect("Failed to record AI usage");
+    }
+
+    let answer = completion.content.trim().to_string();
+    let citations = citations(&answer, &passages);
+
+    let mut tx = pool.begin().await.expect("Failed to start transaction");
+    query!(
+        r#"
+        INSERT INTO chat_messages (thread_id, role, content) VALUES ($1, 'user', $2)
+        "#,
+        thread_id,
+        question
+    )
+    .execute(&mut *tx)
+    .await
+    .expect("Failed to save chat message");
+    let message = query!(
+        r#"
+        INSERT INTO chat_messages (thread_id, role, content, citations) VALUES ($1, 'assistant', $2, $3)
+        RETURNING id, role, content, citations, created_at
+        "#,
+        thread_id,
+        answer,
+        citations
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .expect("Failed to save chat message");
+    let title: String = question.chars().take(100).collect();
+    query!(
+        r#"
+        UPDATE chat_threads SET title = COALESCE(title, $2), updated_at = NOW() WHERE id = $1
+        "#,
+        thread_id,
+        title
+    )
+    .execute(&mut *tx)
+    .await
+    .expect("Failed to update chat");
+    tx.commit().await.expect("Failed to commit transaction");
+
+    (StatusCode::OK, Json(ChatMessageResponse {
+        message: Some(ChatMessage {
+            id: message.id.to_string(),
+            role: message.role,
+            content: message.content,
+            citations: message.citations,
+            created_at: message.created_at.to_string(),
+        }),
+        error: None,
+    }))
+}
+
+fn system_prompt(doc_title: Option<&str>, passages: &[Passage]) -> String {
+    let scope = match doc_title {
+        Some(title) => format!("the user's document \"{}\"", title),
+        None => "the user's notes".to_string(),
+    };
+    let mut prompt = format!(concat!(
+        "You answer questions about {}, using only the numbered excerpts below. ",
+        "Cite the excerpts you use inline by number, like [1] or [2][3]. ",
+        "If they don't contain the answer, say that you couldn't find it in the notes rather than guessing.\n\n",
+        "Excerpts:"
+    ), scope);
+    if passages.is_empty() {
+        prompt.push_str("\n\n(none)");
+    }
+    for (index, passage) in passages.iter().enumerate() {
+        prompt.push_str(&format!("\n\n[{}] From \"{}\":\n{}", index + 1, passage.title, passage.text));
+    }
+    prompt
+}
+
+/// The passages cited as `[n]` in the answer, in order of first mention.
+fn citations(answer: &str, passages: &[Passage]) -> serde_json::Value {
+    let mut cited: Vec<usize> = Vec::new();
+    for (start, _) in answer.match_indices('[') {
+        let rest = &answer[start + 1..];
+        let Some(end) = rest.find(']') else {
+            continue;
+        };
+        let Ok(index) = rest[..end].trim().parse::<usize>() else {
+            continue;
+        };
+        if (1..=passages.len()).contains(&index) && !cited.contains(&index) {
+            cited.push(index);
+        }
+    }
+
+    cited.into_iter().map(|index| {
+        let passage = &passages[index - 1];
+        json!({
+            "index": index,
+            "doc_id": passage.doc_id.to_string(),
+            "title": passage.title,
+            "excerpt": passage.text.chars().take(EXCERPT_CHARS).collect::<String>(),
+        })
+    }).collect()
+}
+
+#[derive(Deserialize)]
+pub struct ChatMessageRequest {
+    content: String,
+}
+
+#[derive(Serialize)]
+pub struct ChatMessageResponse {
+    message: Option<ChatMessage>,
+    error: Option<String>,
+}
@@ -0,0 +1,104 @@
+use axum::{
+    Json,
+    Extension, http::StatusCode,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, query};
+use uuid::Uuid;
+
+use crate::auth::CurrentUser;
+
+pub async fn get_handler(Extension(pool): Extension<PgPool>, Extension(auth_user): Extension<CurrentUser>) -> (StatusCode, Json<ThreadsResponse>) {
+    let threads = query!(
+        r#"
+        SELECT id, doc_id, title, created_at, updated_at FROM chat_threads
+        WHERE user_id = $1
+        ORDER BY updated_at DESC
+        "#,
+        Uuid::parse_str(&auth_user.id).unwrap()
+    )
+    .fetch_all(&pool)
+    .await
+    .expect("Failed to fetch chats");
+
+    let threads = threads.into_iter().map(|thread| Thread {
+        id: thread.id.to_string(),
+        doc_id: thread.doc_id.map(|id| id.to_string()),
+        title: thread.title,
+        created_at: thread.created_at.to_string(),
+        updated_at: thread.updated_at.to_string(),
+    }).collect();
+
+    (StatusCode::OK, Json(ThreadsResponse { threads, error: None }))
+}
+
+/// Starts a conversation about one document, or about all of the user's
+/// documents when `doc_id` is left out.
+pub async fn post_handler(
+    Extension(pool): Extension<PgPool>,
+    Extension(auth_user): Extension<CurrentUser>,
+    Json(payload): Json<ThreadRequest>
+) -> (StatusCode, Json<ThreadResponse>) {
+    let user_id = Uuid::parse_str(&auth_user.id).unwrap();
+    let doc_id = match payload.doc_id.as_deref().map(Uuid::parse_str) {
+        Some(Ok(doc_id)) => Some(doc_id),
+        Some(Err(_)) => return (StatusCode::NOT_FOUND, Json(ThreadResponse { thread: None, error: Some("Document not found".to_string()) })),
+        None => None,
+    };
+
+    let thread = query!(
+        r#"
+        INSERT INTO chat_threads (user_id, doc_id)
+        SELECT $1, $2
+        WHERE $2::uuid IS NULL OR EXISTS (SELECT 1 FROM docs WHERE id = $2 AND user_id = $1)
+        RETURNING id, doc_id, title, created_at, updated_at
+        "#,
+        user_id,
+        doc_id
+    )
+    .fetch_optional(&pool)
+    .await
+    .expect("Failed to create chat");
+
+    match thread {
+        Some(thread) => (StatusCode::CREATED, Json(ThreadResponse {
+            thread: Some(Thread {
+                id: thread.id.to_string(),
+                doc_id: thread.doc_id.map(|id| id.to_string()),
+                title: thread.title,
+                created_at: thread.created_at.to_string(),
+                updated_at: thread.updated_at.to_string(),
+            }),
+            error: None,
+        })),
+        None => (StatusCode::NOT_FOUND, Json(ThreadResponse { thread: None, error: Some("Document not found".to_string()) })),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ThreadRequest {
+    doc_id: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct ThreadsResponse {
+    threads: Vec<Thread>,
+    error: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct ThreadResponse {
+    thread: Option<Thread>,
+    error: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct Thread {
+    pub id: String,
+    /// The document the conversation is about, `null` for the whole notebook.
+    pub doc_id: Option<String>,
+    /// Taken from the first question once one is asked.
+    pub title: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
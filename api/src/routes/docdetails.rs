@@ -8,7 +8,10 @@ use serde_json::Value;
 use sqlx::{PgPool, query};
 use uuid::Uuid;
 
+use crate::ai::Provider;
 use crate::auth::CurrentUser;
+use crate::embeddings;
+use crate::revisions;
 
 pub async fn get_handler(
     Path(doc_id): Path<String>,
@@ -17,7 +20,7 @@ pub async fn get_handler(
 ) -> (StatusCode, Json<DocResponse>) {
     let docs = query!(
         r#"
-        SELECT * FROM docs WHERE user_id = $1 AND id = $2
+        SELECT id, user_id, title, content_text, content_json, content_html, created_at, updated_at FROM docs WHERE user_id = $1 AND id = $2
         "#,
         Uuid::parse_str(&auth_user.id).unwrap(),
         Uuid::parse_str(&doc_id).unwrap()
@@ -36,10 +39,10 @@ pub async fn get_handler(
                 created_at: doc.created_at.expect("Failed to parse created_at").to_string(),
                 updated_at: doc.updated_at.expect("Failed to parse updated_at").to_string(),
             };
-            return (StatusCode::OK, Json(DocResponse { doc: Some(doc), error: None }));
+            (StatusCode::OK, Json(DocResponse { doc: Some(doc), error: None }))
         }
         Err(_) => {
-            return (StatusCode::NOT_FOUND, Json(DocResponse { doc: None, error: Some("Document not found".to_string()) }));
+            (StatusCode::NOT_FOUND, Json(DocResponse { doc: None, error: Some("Document not found".to_string()) }))
         }
     }
 }
@@ -47,25 +50,41 @@ pub async fn get_handler(
 pub async fn put_handler(
     Path(doc_id): Path<String>,
     Extension(pool): Extension<PgPool>,
+    Extension(provider): Extension<Provider>,
     Extension(auth_user): Extension<CurrentUser>,
     Json(payload): Json<DocRequest>
 ) -> (StatusCode, Json<DocResponse>) {
+    let user_id = Uuid::parse_str(&auth_user.id).unwrap();
+    let mut tx = pool.begin().await.expect("Failed to start transaction");
+    // Embedding costs quota, so a save that only changes formatting keeps the
+    // chunks it has.
     let doc = query!(
         r#"
-        UPDATE docs SET title = $1, content_text = $2, content_json = $3, content_html = $4 WHERE id = $5 AND user_id = $6
-        RETURNING id, user_id, title, content_text, content_json, content_html, created_at, updated_at
+        WITH old AS (
+            SELECT id, title, content_text FROM docs WHERE id = $5 AND user_id = $6 FOR UPDATE
+        )
+        UPDATE docs SET title = $1, content_text = $2, content_json = $3, content_html = $4, updated_at = NOW()
+        FROM old
+        WHERE docs.id = old.id
+        RETURNING docs.id, docs.user_id, docs.title, docs.content_text, docs.content_json, docs.content_html, docs.created_at, docs.updated_at,
+            (docs.title <> old.title OR docs.content_text <> old.content_text) AS "reindex!"
         "#,
         payload.title,
         payload.content_text,
         payload.content_json,
         payload.content_html,
         Uuid::parse_str(&doc_id).unwrap(),
-        Uuid::parse_str(&auth_user.id).unwrap()
+        user_id
     )
-    .fetch_one(&pool);
+    .fetch_one(&mut *tx);
 
     match doc.await {
         Ok(doc) => {
+            revisions::record(&mut tx, doc.id, user_id, true).await.expect("Failed to record revision");
+            tx.commit().await.expect("Failed to commit transaction");
+            if doc.reindex {
+                embeddings::spawn_index(pool, provider, user_id, doc.id);
+            }
             let doc = Doc {
                 id: doc.id.to_string(),
                 user_id: doc.user_id.to_string(),
@@ -76,10 +95,10 @@ pub async fn put_handler(
                 created_at: doc.created_at.expect("Failed to parse created_at").to_string(),
                 updated_at: doc.updated_at.expect("Failed to parse updated_at").to_string(),
             };
-            return (StatusCode::OK, Json(DocResponse { doc: Some(doc), error: None }));
+            (StatusCode::OK, Json(DocResponse { doc: Some(doc), error: None }))
         }
         Err(_) => {
-            return (StatusCode::NOT_FOUND, Json(DocResponse { doc: None, error: Some("Document not found".to_string()) }));
+            (StatusCode::NOT_FOUND, Json(DocResponse { doc: None, error: Some("Document not found".to_string()) }))
         }
     }
 }
@@ -111,10 +130,10 @@ pub async fn delete_handler(
                 created_at: doc.created_at.expect("Failed to parse created_at").to_string(),
                 updated_at: doc.updated_at.expect("Failed to parse updated_at").to_string(),
             };
-            return (StatusCode::OK, Json(DocResponse { doc: Some(doc), error: None }));
+            (StatusCode::OK, Json(DocResponse { doc: Some(doc), error: None }))
         }
         Err(_) => {
-            return (StatusCode::NOT_FOUND, Json(DocResponse { doc: None, error: Some("Document not found".to_string()) }));
+            (StatusCode::NOT_FOUND, Json(DocResponse { doc: None, error: Some("Document not found".to_string()) }))
         }
     }
 }
@@ -129,8 +148,8 @@ pub struct DocRequest {
 
 #[derive(Serialize)]
 pub struct DocResponse {
-    doc: Option<Doc>,
-    error: Option<String>,
+    pub doc: Option<Doc>,
+    pub error: Option<String>,
 }
 
 #[derive(Serialize)]
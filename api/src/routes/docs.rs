@@ -3,26 +3,125 @@ use axum::{
     Extension, http::StatusCode,
     extract::Query,
 };
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use sha2::{Digest, Sha256};
 use sqlx::{PgPool, query};
 use uuid::Uuid;
 
+use crate::ai::Provider;
 use crate::auth::CurrentUser;
+use crate::embeddings;
+use crate::revisions;
+use crate::usage;
 
-pub async fn get_handler(Extension(pool): Extension<PgPool>, Extension(auth_user): Extension<CurrentUser>, Query(params): Query<Params>) -> (StatusCode, Json<DocsResponse>) {
+/// Closest documents a `semantic` or `hybrid` search pages through.
+const SEMANTIC_RESULTS: i64 = 100;
+/// Closest chunks those documents are picked from, several per document so
+/// ones matching in many places don't crowd the rest out.
+const SEMANTIC_CHUNKS: i64 = 500;
+/// Constant of the reciprocal rank fusion in `hybrid` searches, damping how
+/// much the very top results of either ranking dominate.
+const RRF_K: f64 = 60.0;
+/// Lowest `word_similarity` of the search to a title for it to count as a
+/// typo of that title, set as `pg_trgm.word_similarity_threshold` so `<%`
+/// can use the trigram index on `lower(title)`.
+const FUZZY_THRESHOLD: &str = "0.4";
+/// `ts_headline` options for search snippets. Matches are wrapped in control
+/// characters rather than tags, so `highlight` can escape the text first.
+const HEADLINE_OPTIONS: &str = "StartSel=\u{2}, StopSel=\u{3}, MaxFragments=2, MaxWords=20, MinWords=8, FragmentDelimiter=\" … \"";
+/// Documents per page when `limit` isn't given.
+const DEFAULT_LIMIT: i64 = 50;
+/// Most documents a page may have.
+const MAX_LIMIT: i64 = 100;
+
+/// Lists the user's documents, or searches them when `search` is given.
+///
+/// `mode` picks how: `keyword` (the default) matches words, ranking title
+/// matches above body ones, along with titles that start with the search or
+/// are close to it despite typos, `semantic` ranks documents by how close their
+/// closest chunk's embedding is to the search's, and `hybrid` fuses both
+/// rankings. `search` is parsed like a web search engine would, so
+/// `"exact phrase"`, `or` and `-word` work. Results come with a `snippet` of
+/// the matching text instead of the whole content, and their `score`.
+///
+/// Results are paged: at most `limit` are returned, ordered by `sort`
+/// (`created`, `updated`, `title`, or `relevance` when searching) in `order`
+/// (`asc` or `desc`), and `next_cursor` is set when there are more, to be
+/// passed back as `cursor`.
+pub async fn get_handler(Extension(pool): Extension<PgPool>, Extension(provider): Extension<Provider>, Extension(auth_user): Extension<CurrentUser>, Query(params): Query<Params>) -> (StatusCode, Json<DocsResponse>) {
+    let user_id = Uuid::parse_str(&auth_user.id).unwrap();
     let search = params.search.unwrap_or_default();
+    let mode = params.mode.as_deref().unwrap_or("keyword");
+    if !["keyword", "semantic", "hybrid"].contains(&mode) {
+        return bad_request("Unknown search mode, expected keyword, semantic or hybrid");
+    }
+    let sort = params.sort.as_deref().unwrap_or(if search.is_empty() { "created" } else { "relevance" });
+    if search.is_empty() && !["created", "updated", "title"].contains(&sort) {
+        return bad_request("Unknown sort, expected created, updated or title");
+    }
+    if !["created", "updated", "title", "relevance"].contains(&sort) {
+        return bad_request("Unknown sort, expected relevance, created, updated or title");
+    }
+    let asc = match params.order.as_deref() {
+        Some("asc") => true,
+        Some("desc") => false,
+        Some(_) => return bad_request("Unknown order, expected asc or desc"),
+        None => sort == "title",
+    };
+    let limit = params.limit.unwrap_or(DEFAULT_LIMIT);
+    if !(1..=MAX_LIMIT).contains(&limit) {
+        return bad_request(&format!("Limit must be between 1 and {}", MAX_LIMIT));
+    }
+    let search_hash = Cursor::search_hash(&search, mode);
+    let cursor = match params.cursor.as_deref().map(|cursor| Cursor::decode(cursor, sort, asc, &search_hash)) {
+        Some(Some(cursor)) => Some(cursor),
+        Some(_) => return bad_request("Invalid cursor"),
+        None => None,
+    };
+
     if search.is_empty() {
-        let docs = query!(
+        // Sort keys are text, with timestamps in a format that sorts the
+        // same way, so one keyset condition covers every sort.
+        let (after_key, after_id) = match cursor {
+            Some(Cursor { key, id, .. }) => (Some(key), Some(id)),
+            None => (None, None),
+        };
+        let mut docs = query!(
             r#"
-            SELECT id, title FROM docs WHERE user_id = $1 ORDER BY created_at DESC
+            SELECT id, title, sort_key AS "sort_key!" FROM (
+                SELECT id, title, COALESCE(CASE $2
+                    WHEN 'title' THEN lower(title)
+                    WHEN 'updated' THEN to_char(updated_at, 'YYYY-MM-DD HH24:MI:SS.US')
+                    ELSE to_char(created_at, 'YYYY-MM-DD HH24:MI:SS.US')
+                END, '') AS sort_key
+                FROM docs WHERE user_id = $1
+            ) docs
+            WHERE $4::text IS NULL OR CASE WHEN $3 THEN (sort_key, id) > ($4, $5) ELSE (sort_key, id) < ($4, $5) END
+            ORDER BY
+                CASE WHEN $3 THEN sort_key END, CASE WHEN $3 THEN id END,
+                CASE WHEN NOT $3 THEN sort_key END DESC, CASE WHEN NOT $3 THEN id END DESC
+            LIMIT $6
             "#,
-            Uuid::parse_str(&auth_user.id).unwrap()
+            user_id,
+            sort,
+            asc,
+            after_key,
+            after_id,
+            limit + 1
         )
             .fetch_all(&pool)
             .await
             .expect("Failed to fetch docs");
 
+        let next_cursor = if docs.len() as i64 > limit {
+            docs.truncate(limit as usize);
+            docs.last().map(|doc| Cursor { sort: sort.to_string(), asc, search: search_hash, score: None, key: doc.sort_key.clone(), id: doc.id }.encode())
+        } else {
+            None
+        };
+
         let docs: Vec<Doc> = docs.into_iter().map(|doc| Doc {
             id: doc.id.to_string(),
             title: doc.title,
@@ -30,40 +129,319 @@ pub async fn get_handler(Extension(pool): Extension<PgPool>, Extension(auth_user
             content_text: None,
             content_json: None,
             content_html: None,
+            snippet: None,
+            score: None,
             created_at: None,
             updated_at: None,
         }).collect();
 
-        (StatusCode::OK, Json(DocsResponse { docs, error: None }))
+        return (StatusCode::OK, Json(DocsResponse { docs, next_cursor, error: None }));
+    }
+
+    // Searches seek the same way, with the score first when sorting by
+    // relevance.
+    let (after_score, after_key, after_id) = match cursor {
+        Some(Cursor { score: Some(score), key, id, .. }) => (Some(score), Some(key), Some(id)),
+        Some(_) => return bad_request("Invalid cursor"),
+        None => (None, None, None),
+    };
+
+    let mut docs: Vec<(Doc, f64, String, Uuid)> = if mode != "keyword" {
+        // Embedding the search is an AI request like any other.
+        let usage_id = match usage::reserve(&pool, user_id, provider.embedding_model())
+            .await
+            .expect("Failed to check AI usage")
+        {
+            Ok(usage_id) => usage_id,
+            Err(error) => return (StatusCode::TOO_MANY_REQUESTS, Json(DocsResponse { docs: Vec::new(), next_cursor: None, error: Some(error) })),
+        };
+        let embedding = match provider.embed(vec![search.clone()]).await {
+            Ok(mut embeddings) => embeddings.remove(0),
+            Err(err) => return (err.status(), Json(DocsResponse { docs: Vec::new(), next_cursor: None, error: Some(err.to_string()) })),
+        };
+        usage::record(&pool, usage_id, usage::estimate_tokens(search.chars().count()), 0)
+            .await
+            .expect("Failed to record AI usage");
+
+        if mode == "semantic" {
+            query!(
+                r#"
+                WITH nearest AS (
+                    SELECT doc_id, embedding <#> $3::real[]::vector AS distance
+                    FROM doc_chunks
+                    WHERE user_id = $1 AND model = $2
+                    ORDER BY distance
+                    LIMIT $13
+                ), matches AS (
+                    SELECT doc_id AS id, -min(distance) AS score
+                    FROM nearest
+                    GROUP BY doc_id
+                    ORDER BY score DESC
+                    LIMIT $4
+                )
+                SELECT id, title, snippet AS "snippet!", score AS "score!", score_key AS "score_key!", sort_key AS "sort_key!" FROM (
+                    SELECT docs.id, docs.title,
+                        ts_headline('english', docs.content_text, websearch_to_tsquery('english', $5), $6) AS snippet,
+                        matches.score,
+                        CASE WHEN $7 = 'relevance' THEN matches.score ELSE 0 END AS score_key,
+                        COALESCE(CASE $7
+                            WHEN 'title' THEN lower(docs.title)
+                            WHEN 'updated' THEN to_char(docs.updated_at, 'YYYY-MM-DD HH24:MI:SS.US')
+                            WHEN 'created' THEN to_char(docs.created_at, 'YYYY-MM-DD HH24:MI:SS.US')
+                        END, '') AS sort_key
+                    FROM matches JOIN docs ON docs.id = matches.id
+                ) results
+                WHERE $10::text IS NULL OR CASE WHEN $8 THEN (score_key, sort_key, id) > ($9, $10, $11) ELSE (score_key, sort_key, id) < ($9, $10, $11) END
+                ORDER BY
+                    CASE WHEN $8 THEN score_key END, CASE WHEN $8 THEN sort_key END, CASE WHEN $8 THEN id END,
+                    CASE WHEN NOT $8 THEN score_key END DESC, CASE WHEN NOT $8 THEN sort_key END DESC, CASE WHEN NOT $8 THEN id END DESC
+                LIMIT $12
+                "#,
+                user_id,
+                provider.embedding_model(),
+                &embedding,
+                SEMANTIC_RESULTS,
+                search,
+                HEADLINE_OPTIONS,
+                sort,
+                asc,
+                after_score,
+                after_key,
+                after_id,
+                limit + 1,
+                SEMANTIC_CHUNKS
+            )
+                .fetch_all(&pool)
+                .await
+                .expect("Failed to search docs")
+                .into_iter()
+                .map(|doc| (found(doc.id, doc.title, &doc.snippet, doc.score), doc.score_key, doc.sort_key, doc.id))
+                .collect()
+        } else {
+            query!(
+                r#"
+                WITH nearest AS (
+                    SELECT doc_id, embedding <#> $3::real[]::vector AS distance
+                    FROM doc_chunks
+                    WHERE user_id = $1 AND model = $2
+                    ORDER BY distance
+                    LIMIT $14
+                ), semantic AS (
+                    SELECT doc_id, row_number() OVER (ORDER BY min(distance)) AS rank
+                    FROM nearest
+                    GROUP BY doc_id
+                    ORDER BY rank
+                    LIMIT $4
+                ), keyword AS (
+                    SELECT id AS doc_id, row_number() OVER (ORDER BY ts_rank(search, query) DESC) AS rank
+                    FROM docs, websearch_to_tsquery('english', $5) AS query
+                    WHERE user_id = $1 AND search @@ query
+                    ORDER BY rank
+                    LIMIT $4
+                ), matches AS (
+                    SELECT COALESCE(semantic.doc_id, keyword.doc_id) AS id,
+                        COALESCE(1 / ($6::float8 + semantic.rank), 0) + COALESCE(1 / ($6::float8 + keyword.rank), 0) AS score
+                    FROM semantic FULL JOIN keyword ON keyword.doc_id = semantic.doc_id
+                    ORDER BY score DESC
+                    LIMIT $4
+                )
+                SELECT id, title, snippet AS "snippet!", score AS "score!", score_key AS "score_key!", sort_key AS "sort_key!" FROM (
+                    SELECT docs.id, docs.title,
+                        ts_headline('english', docs.content_text, websearch_to_tsquery('english', $5), $7) AS snippet,
+                        matches.score,
+                        CASE WHEN $8 = 'relevance' THEN matches.score ELSE 0 END AS score_key,
+                        COALESCE(CASE $8
+                            WHEN 'title' THEN lower(docs.title)
+                            WHEN 'updated' THEN to_char(docs.updated_at, 'YYYY-MM-DD HH24:MI:SS.US')
+                            WHEN 'created' THEN to_char(docs.created_at, 'YYYY-MM-DD HH24:MI:SS.US')
+                        END, '') AS sort_key
+                    FROM matches JOIN docs ON docs.id = matches.id
+                ) results
+                WHERE $11::text IS NULL OR CASE WHEN $9 THEN (score_key, sort_key, id) > ($10, $11, $12) ELSE (score_key, sort_key, id) < ($10, $11, $12) END
+                ORDER BY
+                    CASE WHEN $9 THEN score_key END, CASE WHEN $9 THEN sort_key END, CASE WHEN $9 THEN id END,
+                    CASE WHEN NOT $9 THEN score_key END DESC, CASE WHEN NOT $9 THEN sort_key END DESC, CASE WHEN NOT $9 THEN id END DESC
+                LIMIT $13
+                "#,
+                user_id,
+                provider.embedding_model(),
+                &embedding,
+                SEMANTIC_RESULTS,
+                search,
+                RRF_K,
+                HEADLINE_OPTIONS,
+                sort,
+                asc,
+                after_score,
+                after_key,
+                after_id,
+                limit + 1,
+                SEMANTIC_CHUNKS
+            )
+                .fetch_all(&pool)
+                .await
+                .expect("Failed to search docs")
+                .into_iter()
+                .map(|doc| (found(doc.id, doc.title, &doc.snippet, doc.score), doc.score_key, doc.sort_key, doc.id))
+                .collect()
+        }
     } else {
+        // Full-text matches score their rank scaled to 0-1, title matches 1
+        // for a prefix of the title or one of its words and their similarity
+        // otherwise, so a title match outranks a mention in the body.
+        let prefix = search.trim().to_lowercase().replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_");
+        let mut tx = pool.begin().await.expect("Failed to start transaction");
+        query!(
+            r#"
+            SELECT set_config('pg_trgm.word_similarity_threshold', $1, true)
+            "#,
+            FUZZY_THRESHOLD
+        )
+            .fetch_one(&mut *tx)
+            .await
+            .expect("Failed to set similarity threshold");
         let docs = query!(
             r#"
-            SELECT id, title, content_text FROM docs WHERE user_id = $1 AND (content_text @@ to_tsquery($2) OR title @@ to_tsquery($2)) ORDER BY created_at DESC
+            WITH matches AS (
+                SELECT id, (CASE WHEN search @@ query THEN ts_rank(search, query, 32) ELSE 0 END
+                    + CASE
+                        WHEN lower(title) LIKE $4 || '%' OR lower(title) LIKE '% ' || $4 || '%' THEN 1
+                        WHEN $2 <% lower(title) THEN word_similarity($2, lower(title))
+                        ELSE 0
+                    END)::float8 AS score
+                FROM docs, websearch_to_tsquery('english', $2) AS query
+                WHERE user_id = $1
+                    AND (search @@ query OR lower(title) LIKE $4 || '%' OR lower(title) LIKE '% ' || $4 || '%' OR $2 <% lower(title))
+            )
+            SELECT id, title, snippet AS "snippet!", score AS "score!", score_key AS "score_key!", sort_key AS "sort_key!" FROM (
+                SELECT docs.id, docs.title,
+                    ts_headline('english', docs.content_text, websearch_to_tsquery('english', $2), $3) AS snippet,
+                    matches.score,
+                    CASE WHEN $5 = 'relevance' THEN matches.score ELSE 0 END AS score_key,
+                    COALESCE(CASE $5
+                        WHEN 'title' THEN lower(docs.title)
+                        WHEN 'updated' THEN to_char(docs.updated_at, 'YYYY-MM-DD HH24:MI:SS.US')
+                        WHEN 'created' THEN to_char(docs.created_at, 'YYYY-MM-DD HH24:MI:SS.US')
+                    END, '') AS sort_key
+                FROM matches JOIN docs ON docs.id = matches.id
+            ) results
+            WHERE $8::text IS NULL OR CASE WHEN $6 THEN (score_key, sort_key, id) > ($7, $8, $9) ELSE (score_key, sort_key, id) < ($7, $8, $9) END
+            ORDER BY
+                CASE WHEN $6 THEN score_key END, CASE WHEN $6 THEN sort_key END, CASE WHEN $6 THEN id END,
+                CASE WHEN NOT $6 THEN score_key END DESC, CASE WHEN NOT $6 THEN sort_key END DESC, CASE WHEN NOT $6 THEN id END DESC
+            LIMIT $10
             "#,
-            Uuid::parse_str(&auth_user.id).unwrap(),
-            search
+            user_id,
+            search,
+            HEADLINE_OPTIONS,
+            prefix,
+            sort,
+            asc,
+            after_score,
+            after_key,
+            after_id,
+            limit + 1
         )
-            .fetch_all(&pool)
+            .fetch_all(&mut *tx)
             .await
             .expect("Failed to fetch docs");
+        tx.commit().await.expect("Failed to commit transaction");
+        docs.into_iter()
+            .map(|doc| (found(doc.id, doc.title, &doc.snippet, doc.score), doc.score_key, doc.sort_key, doc.id))
+            .collect()
+    };
 
-        let docs: Vec<Doc> = docs.into_iter().map(|doc| Doc {
-            id: doc.id.to_string(),
-            title: doc.title,
-            user_id: None,
-            content_text: Some(doc.content_text),
-            content_json: None,
-            content_html: None,
-            created_at: None,
-            updated_at: None,
-        }).collect();
+    let next_cursor = if docs.len() as i64 > limit {
+        docs.truncate(limit as usize);
+        docs.last().map(|(_, score_key, sort_key, id)| Cursor {
+            sort: sort.to_string(),
+            asc,
+            search: search_hash.clone(),
+            score: Some(*score_key),
+            key: sort_key.clone(),
+            id: *id,
+        }.encode())
+    } else {
+        None
+    };
+    let docs = docs.into_iter().map(|(doc, ..)| doc).collect();
+
+    (StatusCode::OK, Json(DocsResponse { docs, next_cursor, error: None }))
+}
+
+fn bad_request(error: &str) -> (StatusCode, Json<DocsResponse>) {
+    (StatusCode::BAD_REQUEST, Json(DocsResponse { docs: Vec::new(), next_cursor: None, error: Some(error.to_string()) }))
+}
+
+/// Where a page ended, handed to clients as an opaque `next_cursor`. The
+/// next page continues after the `score` (for searches), sort `key` and `id`
+/// of the last document. The sort and a hash of the search are kept to reject
+/// cursors from another listing.
+#[derive(Serialize, Deserialize)]
+struct Cursor {
+    sort: String,
+    asc: bool,
+    search: String,
+    score: Option<f64>,
+    key: String,
+    id: Uuid,
+}
+
+impl Cursor {
+    fn search_hash(search: &str, mode: &str) -> String {
+        URL_SAFE_NO_PAD.encode(&Sha256::digest(format!("{}\n{}", mode, search).as_bytes())[..12])
+    }
+
+    fn encode(&self) -> String {
+        URL_SAFE_NO_PAD.encode(serde_json::to_vec(self).expect("Failed to encode cursor"))
+    }
+
+    /// Decodes a cursor, or `None` if it is malformed or was issued for
+    /// another sort, order or search.
+    fn decode(cursor: &str, sort: &str, asc: bool, search_hash: &str) -> Option<Cursor> {
+        let bytes = URL_SAFE_NO_PAD.decode(cursor).ok()?;
+        let cursor: Cursor = serde_json::from_slice(&bytes).ok()?;
+        (cursor.sort == sort && cursor.asc == asc && cursor.search == search_hash).then_some(cursor)
+    }
+}
 
-        (StatusCode::OK, Json(DocsResponse { docs, error: None }))
+fn found(id: Uuid, title: String, snippet: &str, score: f64) -> Doc {
+    Doc {
+        id: id.to_string(),
+        title,
+        user_id: None,
+        content_text: None,
+        content_json: None,
+        content_html: None,
+        snippet: Some(highlight(snippet)),
+        score: Some(score),
+        created_at: None,
+        updated_at: None,
     }
 }
 
+/// Escapes a `ts_headline` snippet as HTML, turning its match markers into
+/// `<mark>` tags.
+fn highlight(snippet: &str) -> String {
+    let mut html = String::with_capacity(snippet.len());
+    for c in snippet.chars() {
+        match c {
+            '\u{2}' => html.push_str("<mark>"),
+            '\u{3}' => html.push_str("</mark>"),
+            '&' => html.push_str("&amp;"),
+            '<' => html.push_str("&lt;"),
+            '>' => html.push_str("&gt;"),
+            '"' => html.push_str("&quot;"),
+            '\'' => html.push_str("&#39;"),
+            c => html.push(c),
+        }
+    }
+    html
+}
+
 pub async fn post_handler(
     Extension(pool): Extension<PgPool>,
+    Extension(provider): Extension<Provider>,
     Extension(auth_user): Extension<CurrentUser>,
     Json(payload): Json<DocsRequest>
 ) -> (StatusCode, Json<DocResponse>) {
@@ -75,6 +453,7 @@ pub async fn post_handler(
         user_id: auth_user.id.to_string()
     };
 
+    let mut tx = pool.begin().await.expect("Failed to start transaction");
     let doc = query!(
         r#"
         INSERT INTO docs (user_id, title, content_text, content_json, content_html)
@@ -87,9 +466,12 @@ pub async fn post_handler(
         data.content_json,
         data.content_html,
     )
-    .fetch_one(&pool)
+    .fetch_one(&mut *tx)
     .await
     .expect("Failed to create doc");
+    revisions::record(&mut tx, doc.id, doc.user_id, false).await.expect("Failed to record revision");
+    tx.commit().await.expect("Failed to commit transaction");
+    embeddings::spawn_index(pool, provider, doc.user_id, doc.id);
 
     let result: Doc = Doc {
         id: doc.id.to_string(),
@@ -98,6 +480,8 @@ pub async fn post_handler(
         content_text: Some(doc.content_text),
         content_json: Some(doc.content_json),
         content_html: Some(doc.content_html),
+        snippet: None,
+        score: None,
         created_at: Some(doc.created_at.expect("Failed to parse created_at").to_string()),
         updated_at: Some(doc.updated_at.expect("Failed to parse updated_at").to_string()),
     };
@@ -108,6 +492,15 @@ pub async fn post_handler(
 #[derive(Deserialize)]
 pub struct Params {
     pub search: Option<String>,
+    /// `keyword`, `semantic` or `hybrid`.
+    pub mode: Option<String>,
+    /// `created`, `updated`, `title`, or `relevance` when searching.
+    pub sort: Option<String>,
+    /// `asc` or `desc`.
+    pub order: Option<String>,
+    pub limit: Option<i64>,
+    /// `next_cursor` of the previous page.
+    pub cursor: Option<String>,
 }
 
 #[derive(Deserialize)]
@@ -121,6 +514,7 @@ pub struct DocsRequest {
 #[derive(Serialize)]
 pub struct DocsResponse {
     docs: Vec<Doc>,
+    next_cursor: Option<String>,
     error: Option<String>,
 }
 
@@ -138,6 +532,10 @@ pub struct Doc {
     pub content_text: Option<String>,
     pub content_json: Option<Value>,
     pub content_html: Option<String>,
+    /// HTML excerpt of a search result with the matches in `<mark>` tags.
+    pub snippet: Option<String>,
+    /// How well a search result matches; higher is better.
+    pub score: Option<f64>,
     pub created_at: Option<String>,
     pub updated_at: Option<String>,
 }
@@ -150,3 +548,52 @@ pub struct CreateDoc {
     pub content_json: Value,
     pub content_html: String,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cursor(sort: &str, asc: bool, search: &str, mode: &str) -> Cursor {
+        Cursor {
+            sort: sort.to_string(),
+            asc,
+            search: Cursor::search_hash(search, mode),
+            score: Some(0.25),
+            key: "2026-10-18 12:00:00.000000".to_string(),
+            id: Uuid::nil(),
+        }
+    }
+
+    #[test]
+    fn cursor_round_trips() {
+        let encoded = cursor("relevance", false, "garden", "keyword").encode();
+        let hash = Cursor::search_hash("garden", "keyword");
+        let decoded = Cursor::decode(&encoded, "relevance", false, &hash).expect("cursor should decode");
+        assert_eq!(decoded.score, Some(0.25));
+        assert_eq!(decoded.key, "2026-10-18 12:00:00.000000");
+        assert_eq!(decoded.id, Uuid::nil());
+    }
+
+    #[test]
+    fn cursor_is_rejected_for_another_listing() {
+        let encoded = cursor("relevance", false, "garden", "keyword").encode();
+        let hash = Cursor::search_hash("garden", "keyword");
+        assert!(Cursor::decode(&encoded, "title", false, &hash).is_none());
+        assert!(Cursor::decode(&encoded, "relevance", true, &hash).is_none());
+        assert!(Cursor::decode(&encoded, "relevance", false, &Cursor::search_hash("tomato", "keyword")).is_none());
+        assert!(Cursor::decode(&encoded, "relevance", false, &Cursor::search_hash("garden", "semantic")).is_none());
+    }
+
+    #[test]
+    fn malformed_cursor_is_rejected() {
+        assert!(Cursor::decode("not a cursor", "created", false, &Cursor::search_hash("", "keyword")).is_none());
+        assert!(Cursor::decode(&URL_SAFE_NO_PAD.encode(b"{}"), "created", false, &Cursor::search_hash("", "keyword")).is_none());
+    }
+
+    #[test]
+    fn highlight_escapes_around_matches() {
+        assert_eq!(highlight("a<b \u{2}R&D\u{3}>c"), "a&lt;b <mark>R&amp;D</mark>&gt;c");
+        assert_eq!(highlight("<\u{2}&\u{3}<"), "&lt;<mark>&amp;</mark>&lt;");
+        assert_eq!(highlight("\"it's\""), "&quot;it&#39;s&quot;");
+    }
+}
@@ -0,0 +1,42 @@
+use axum::{
+    Json,
+    Extension, http::StatusCode,
+    extract::Path,
+};
+use serde::Serialize;
+use sqlx::{PgPool, query};
+use uuid::Uuid;
+
+/// Delivery status of a queued email. Sign-in emails are sent before the user
+/// has a session, so this is public; the random id is the only way to look a
+/// message up, and nothing beyond the status is returned.
+pub async fn get_handler(Path(email_id): Path<String>, Extension(pool): Extension<PgPool>) -> (StatusCode, Json<EmailStatusResponse>) {
+    let Ok(email_id) = Uuid::parse_str(&email_id) else {
+        return (StatusCode::NOT_FOUND, Json(EmailStatusResponse { status: None, error: Some("Email not found".to_string()) }));
+    };
+
+    let email = query!(
+        r#"
+        SELECT status FROM email_outbox WHERE id = $1
+        "#,
+        email_id
+    )
+    .fetch_optional(&pool)
+    .await
+    .expect("Failed to fetch email");
+
+    match email {
+        Some(email) => (StatusCode::OK, Json(EmailStatusResponse {
+            // Dead letters are reported as failed; the details are for operators.
+            status: Some(if email.status == "dead" { "failed".to_string() } else { email.status }),
+            error: None,
+        })),
+        None => (StatusCode::NOT_FOUND, Json(EmailStatusResponse { status: None, error: Some("Email not found".to_string()) })),
+    }
+}
+
+#[derive(Serialize)]
+pub struct EmailStatusResponse {
+    status: Option<String>,
+    error: Option<String>,
+}
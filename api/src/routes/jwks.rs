@@ -0,0 +1,11 @@
+use axum::{
+    Json,
+    http::StatusCode,
+};
+use jsonwebtoken::jwk::JwkSet;
+
+use crate::keys;
+
+pub async fn handler() -> (StatusCode, Json<JwkSet>) {
+    (StatusCode::OK, Json(keys::get().jwks().clone()))
+}
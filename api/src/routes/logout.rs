@@ -0,0 +1,26 @@
+use axum::{
+    Json,
+    Extension, http::StatusCode,
+};
+use serde::Serialize;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::auth::CurrentUser;
+use crate::session;
+
+pub async fn handler(Extension(pool): Extension<PgPool>, Extension(auth_user): Extension<CurrentUser>) -> (StatusCode, Json<LogoutResponse>) {
+    let Some(session_id) = auth_user.session_id else {
+        return (StatusCode::BAD_REQUEST, Json(LogoutResponse { error: Some("Not signed in with a session".to_string()) }));
+    };
+
+    match session::revoke(&pool, Uuid::parse_str(&session_id).unwrap()).await {
+        Ok(_) => (StatusCode::OK, Json(LogoutResponse { error: None })),
+        Err(status) => (status, Json(LogoutResponse { error: Some("Failed to revoke session".to_string()) })),
+    }
+}
+
+#[derive(Serialize)]
+pub struct LogoutResponse {
+    error: Option<String>,
+}
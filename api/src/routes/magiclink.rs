@@ -0,0 +1,64 @@
+use axum::{
+    Json,
+    Extension, http::StatusCode,
+};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::env::var;
+use uuid::Uuid;
+
+use crate::auth::{decode_token, encode_token};
+use crate::routes::otpverify::{claim_attempt, complete_verification, error_response, OtpVerifyResponse};
+use crate::session::ClientInfo;
+
+const PURPOSE: &str = "magic_link";
+
+pub async fn handler(Extension(pool): Extension<PgPool>, client: ClientInfo, Json(payload): Json<MagicLinkRequest>) -> (StatusCode, Json<OtpVerifyResponse>) {
+    let claims = match decode_token::<MagicLinkClaims>(&payload.token) {
+        Ok(data) if data.claims.purpose == PURPOSE => data.claims,
+        _ => return error_response("Invalid or expired sign-in link"),
+    };
+
+    let Ok(code_id) = Uuid::parse_str(&claims.sub) else {
+        return error_response("Invalid or expired sign-in link");
+    };
+
+    // Guessing the authenticator code through the link counts against the
+    // same attempt limit as /otp-verify.
+    if payload.totp_code.is_some() && claim_attempt(&pool, code_id).await.is_none() {
+        return error_response("Too many attempts, please request a new code");
+    }
+
+    complete_verification(&pool, code_id, payload.totp_code.as_deref(), &client).await
+}
+
+/// Builds the sign-in link sent next to the OTP. The link is tied to the OTP
+/// row, so using either one consumes both.
+pub fn magic_link(code_id: Uuid, ttl_minutes: i32) -> Result<String, StatusCode> {
+    let now = Utc::now().timestamp() as usize;
+    let claims = MagicLinkClaims {
+        sub: code_id.to_string(),
+        purpose: PURPOSE.to_string(),
+        iat: now,
+        exp: now + ttl_minutes as usize * 60,
+    };
+    let token = encode_token(&claims)?;
+
+    let app_url = var("APP_URL").ok().filter(|url| !url.is_empty()).unwrap_or_else(|| "http://localhost:3000".to_string());
+    Ok(format!("{}/magic-link?token={}", app_url.trim_end_matches('/'), token))
+}
+
+#[derive(Serialize, Deserialize)]
+struct MagicLinkClaims {
+    sub: String,
+    purpose: String,
+    iat: usize,
+    exp: usize,
+}
+
+#[derive(Deserialize)]
+pub struct MagicLinkRequest {
+    token: String,
+    totp_code: Option<String>,
+}
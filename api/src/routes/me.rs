@@ -2,26 +2,89 @@ use axum::{
     Json,
     Extension, http::StatusCode,
 };
-use serde::{Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
+use sqlx::{PgPool, query};
+use uuid::Uuid;
 
 use crate::auth::CurrentUser;
+use crate::templates::normalize_locale;
+
+pub async fn get_handler(Extension(pool): Extension<PgPool>, Extension(auth_user): Extension<CurrentUser>) -> (StatusCode, Json<MeResponse>) {
+    let user = query!(
+        r#"
+        SELECT id, email, locale FROM users WHERE id = $1
+        "#,
+        Uuid::parse_str(&auth_user.id).unwrap()
+    )
+    .fetch_one(&pool)
+    .await
+    .expect("Failed to fetch user");
 
-pub async fn handler(Extension(auth_user): Extension<CurrentUser>) -> (StatusCode, Json<MeResponse>) {
     (StatusCode::OK, Json(MeResponse {
-        user: User {
-            id: auth_user.id.to_string(),
-            email: auth_user.email.clone(),
-        }
+        user: Some(User {
+            id: user.id.to_string(),
+            email: user.email,
+            locale: user.locale,
+        }),
+        error: None,
     }))
 }
 
+/// Updates account preferences. `locale` picks the language of the emails
+/// the user receives; `null` falls back to the default. Fields left out are
+/// left unchanged.
+pub async fn put_handler(Extension(pool): Extension<PgPool>, Extension(auth_user): Extension<CurrentUser>, Json(payload): Json<MeRequest>) -> (StatusCode, Json<MeResponse>) {
+    let locale = match payload.locale.as_ref().map(|locale| locale.as_deref().map(normalize_locale)) {
+        Some(Some(None)) => return (StatusCode::BAD_REQUEST, Json(MeResponse { user: None, error: Some("Invalid locale".to_string()) })),
+        Some(locale) => locale.flatten(),
+        None => None,
+    };
+
+    let user = query!(
+        r#"
+        UPDATE users SET locale = CASE WHEN $3 THEN $2 ELSE locale END WHERE id = $1
+        RETURNING id, email, locale
+        "#,
+        Uuid::parse_str(&auth_user.id).unwrap(),
+        locale,
+        payload.locale.is_some()
+    )
+    .fetch_one(&pool)
+    .await
+    .expect("Failed to update user");
+
+    (StatusCode::OK, Json(MeResponse {
+        user: Some(User {
+            id: user.id.to_string(),
+            email: user.email,
+            locale: user.locale,
+        }),
+        error: None,
+    }))
+}
+
+#[derive(Deserialize)]
+pub struct MeRequest {
+    /// `None` when left out, `Some(None)` when `null`.
+    #[serde(default, deserialize_with = "present")]
+    locale: Option<Option<String>>,
+}
+
+/// Deserializes a field that is present, even as `null`, into `Some`, so it
+/// can be told apart from one that was left out.
+fn present<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Option<String>>, D::Error> {
+    Option::deserialize(deserializer).map(Some)
+}
+
 #[derive(Serialize)]
 pub struct MeResponse {
-    user: User
+    user: Option<User>,
+    error: Option<String>,
 }
 
 #[derive(Serialize)]
 struct User {
     id: String,
     email: String,
+    locale: Option<String>,
 }
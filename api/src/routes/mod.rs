@@ -4,3 +4,32 @@ pub mod otp;
 pub mod otpverify;
 pub mod prompt;
 pub mod me;
+pub mod tokenrefresh;
+pub mod logout;
+pub mod sessions;
+pub mod sessiondetails;
+pub mod tokens;
+pub mod tokendetails;
+pub mod jwks;
+pub mod magiclink;
+pub mod passkeys;
+pub mod passkeydetails;
+pub mod passkeyregister;
+pub mod passkeylogin;
+pub mod totp;
+pub mod totpconfirm;
+pub mod totprecoverycodes;
+pub mod oidclogin;
+pub mod emaildetails;
+pub mod usage;
+pub mod aiactions;
+pub mod aiactiondetails;
+pub mod style;
+pub mod styleregenerate;
+pub mod chats;
+pub mod chatdetails;
+pub mod chatmessages;
+pub mod revisions;
+pub mod revisiondetails;
+pub mod revisionrestore;
+pub mod revisiondiff;
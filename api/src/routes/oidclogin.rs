@@ -0,0 +1,246 @@
+use axum::{
+    Json,
+    Extension, http::StatusCode,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, query};
+use uuid::Uuid;
+
+use crate::oidc::{IdTokenClaims, Provider};
+use crate::routes::otp::otp_max_attempts;
+use crate::routes::otpverify::{error_response, sign_in, totp_required_response, OtpVerifyResponse};
+use crate::session::{generate_token, hash_token, ClientInfo};
+use crate::totp::{self, Verification};
+
+/// Minutes the user has to finish signing in at the identity provider.
+const LOGIN_TTL_MINUTES: i32 = 10;
+
+pub async fn start_handler(
+    Extension(pool): Extension<PgPool>,
+    Extension(provider): Extension<Option<Provider>>
+) -> (StatusCode, Json<LoginStartResponse>) {
+    let Some(provider) = provider else {
+        return (StatusCode::NOT_FOUND, Json(LoginStartResponse { authorization_url: None, error: Some("Single sign-on is not configured".to_string()) }));
+    };
+
+    let (state, state_hash) = generate_token();
+    let (nonce, _) = generate_token();
+    let (code_verifier, _) = generate_token();
+
+    let authorization_url = match provider.authorization_url(&state, &nonce, &code_verifier).await {
+        Ok(url) => url,
+        Err(err) => return (StatusCode::BAD_GATEWAY, Json(LoginStartResponse { authorization_url: None, error: Some(err) })),
+    };
+
+    query!(
+        r#"
+        DELETE FROM oidc_logins WHERE expires_at < NOW()
+        "#
+    )
+    .execute(&pool)
+    .await
+    .expect("Failed to clean up sign-ins");
+    query!(
+        r#"
+        INSERT INTO oidc_logins (state_hash, nonce, code_verifier, expires_at)
+        VALUES ($1, $2, $3, NOW() + make_interval(mins => $4))
+        "#,
+        state_hash,
+        nonce,
+        code_verifier,
+        LOGIN_TTL_MINUTES
+    )
+    .execute(&pool)
+    .await
+    .expect("Failed to save sign-in");
+
+    (StatusCode::OK, Json(LoginStartResponse { authorization_url: Some(authorization_url), error: None }))
+}
+
+/// Completes the redirect back from the identity provider. Users are matched
+/// by the provider's subject first, then linked or created by verified email.
+///
+/// Accounts with an authenticator also need `totp_code`. The sign-in then
+/// remembers the user, so the client can retry with the code without going
+/// back to the provider.
+pub async fn finish_handler(
+    Extension(pool): Extension<PgPool>,
+    Extension(provider): Extension<Option<Provider>>,
+    client: ClientInfo,
+    Json(payload): Json<LoginFinishRequest>
+) -> (StatusCode, Json<OtpVerifyResponse>) {
+    let Some(provider) = provider else {
+        return error_response("Single sign-on is not configured");
+    };
+
+    let login = query!(
+        r#"
+        SELECT id, nonce, code_verifier, user_id, expires_at > NOW() AS "valid!"
+        FROM oidc_logins WHERE state_hash = $1
+        "#,
+        hash_token(&payload.state)
+    )
+    .fetch_optional(&pool)
+    .await
+    .expect("Failed to fetch sign-in");
+
+    let Some(login) = login.filter(|login| login.valid) else {
+        return error_response("Sign-in not found or expired");
+    };
+
+    let (user_id, email) = match login.user_id {
+        Some(user_id) => query!(
+            r#"
+            SELECT id, email FROM users WHERE id = $1
+            "#,
+            user_id
+        )
+        .fetch_one(&pool)
+        .await
+        .map(|user| (user.id, user.email))
+        .expect("Failed to fetch user"),
+        None => {
+            let claims = match provider.exchange(&payload.code, &login.code_verifier, &login.nonce).await {
+                Ok(claims) => claims,
+                Err(err) => {
+                    finish(&pool, login.id).await;
+                    return error_response(&err);
+                }
+            };
+            match link_user(&pool, &provider, claims).await {
+                Ok(user) => user,
+                Err(message) => {
+                    finish(&pool, login.id).await;
+                    return error_response(message);
+                }
+            }
+        }
+    };
+
+    let totp_enabled = totp::is_enabled(&pool, user_id)
+        .await
+        .expect("Failed to fetch authenticator");
+    if totp_enabled {
+        let Some(totp_code) = payload.totp_code.filter(|code| !code.trim().is_empty()) else {
+            query!(
+                r#"
+                UPDATE oidc_logins SET user_id = $2 WHERE id = $1
+                "#,
+                login.id,
+                user_id
+            )
+            .execute(&pool)
+            .await
+            .expect("Failed to update sign-in");
+            return totp_required_response("Authenticator code is required");
+        };
+
+        let attempt = query!(
+            r#"
+            UPDATE oidc_logins SET user_id = $2, attempts = attempts + 1
+            WHERE id = $1 AND attempts < $3
+            RETURNING attempts
+            "#,
+            login.id,
+            user_id,
+            otp_max_attempts()
+        )
+        .fetch_optional(&pool)
+        .await
+        .expect("Failed to update sign-in");
+        if attempt.is_none() {
+            finish(&pool, login.id).await;
+            return error_response("Too many attempts, please sign in again");
+        }
+
+        match totp::verify(&pool, user_id, &totp_code).await.expect("Failed to verify authenticator code") {
+            Verification::Valid => {}
+            Verification::Invalid => return totp_required_response("Invalid authenticator code"),
+            Verification::Locked => return error_response("Too many authenticator attempts, please try again later"),
+        }
+    }
+
+    finish(&pool, login.id).await;
+    sign_in(&pool, user_id, email, &client).await
+}
+
+/// Finds the user for a verified ID token, linking the identity on first use.
+async fn link_user(pool: &PgPool, provider: &Provider, claims: IdTokenClaims) -> Result<(Uuid, String), &'static str> {
+    let identity = query!(
+        r#"
+        UPDATE oidc_identities SET last_used_at = NOW(), email = COALESCE($3, oidc_identities.email)
+        FROM users
+        WHERE users.id = oidc_identities.user_id AND issuer = $1 AND subject = $2
+        RETURNING users.id, users.email
+        "#,
+        provider.issuer,
+        claims.sub,
+        claims.email
+    )
+    .fetch_optional(pool)
+    .await
+    .expect("Failed to fetch identity");
+
+    if let Some(user) = identity {
+        return Ok((user.id, user.email));
+    }
+
+    // Only a verified address proves the person owns the matching account.
+    let Some(email) = claims.email.filter(|email| claims.email_verified && email.len() <= 100) else {
+        return Err("Your identity provider did not share a verified email address");
+    };
+
+    let user = query!(
+        r#"
+        INSERT INTO users (email, verified_at) VALUES ($1, NOW())
+        ON CONFLICT (email) DO UPDATE SET verified_at = COALESCE(users.verified_at, NOW())
+        RETURNING id, email
+        "#,
+        email
+    )
+    .fetch_one(pool)
+    .await
+    .expect("Failed to create user");
+
+    query!(
+        r#"
+        INSERT INTO oidc_identities (user_id, issuer, subject, email, last_used_at)
+        VALUES ($1, $2, $3, $4, NOW())
+        ON CONFLICT (issuer, subject) DO NOTHING
+        "#,
+        user.id,
+        provider.issuer,
+        claims.sub,
+        user.email
+    )
+    .execute(pool)
+    .await
+    .expect("Failed to link identity");
+
+    Ok((user.id, user.email))
+}
+
+async fn finish(pool: &PgPool, login_id: Uuid) {
+    query!(
+        r#"
+        DELETE FROM oidc_logins WHERE id = $1
+        "#,
+        login_id
+    )
+    .execute(pool)
+    .await
+    .expect("Failed to finish sign-in");
+}
+
+#[derive(Deserialize)]
+pub struct LoginFinishRequest {
+    code: String,
+    state: String,
+    totp_code: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct LoginStartResponse {
+    authorization_url: Option<String>,
+    error: Option<String>,
+}
@@ -9,98 +9,107 @@ use argon2::{
 };
 use axum::{
     Json,
-    Extension, http::StatusCode,
+    Extension,
+    http::{header::ACCEPT_LANGUAGE, HeaderMap, StatusCode},
 };
-use lettre::{Message, SmtpTransport, Transport};
-use lettre::transport::smtp::{authentication::{Credentials}};
-use lettre::message::{MultiPart, SinglePart};
+use lettre::Address;
+use minijinja::context;
 use rand::Rng;
 use serde::{Deserialize, Serialize};
 use std::env::var;
 use sqlx::{PgPool, query};
 
-pub async fn handler(Extension(pool): Extension<PgPool>, Json(payload): Json<OtpRequest>) -> (StatusCode, Json<OtpResponse>) {
+use crate::outbox::Outbox;
+use crate::routes::magiclink::magic_link;
+use crate::templates::{normalize_locale, Templates};
+
+pub async fn handler(
+    Extension(pool): Extension<PgPool>,
+    Extension(outbox): Extension<Outbox>,
+    Extension(templates): Extension<Templates>,
+    headers: HeaderMap,
+    Json(payload): Json<OtpRequest>
+) -> (StatusCode, Json<OtpResponse>) {
+    if payload.email.parse::<Address>().is_err() || payload.email.len() > 100 {
+        return (StatusCode::BAD_REQUEST, Json(OtpResponse { email_id: None, error: Some("Invalid email address".to_string()) }));
+    }
+
+    // New accounts start out in the browser's language.
+    let request_locale = headers
+        .get(ACCEPT_LANGUAGE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(normalize_locale);
+
     let user = query!(
         r#"
-        SELECT * FROM users WHERE email = $1
+        INSERT INTO users (email, locale) VALUES ($1, $2)
+        ON CONFLICT (email) DO UPDATE SET email = EXCLUDED.email
+        RETURNING id, locale
         "#,
-        payload.email
+        payload.email,
+        request_locale
     )
-    .fetch_one(&pool);
+    .fetch_one(&pool)
+    .await
+    .expect("Failed to upsert user");
 
     let password = rand::rng().random_range(100000..=999999).to_string();
-    println!("Generated password: {}", password);
 
     let salt_str = SaltString::generate(&mut OsRng);
     let salt: Salt = salt_str.as_str().try_into().unwrap();
     let argon2 = Argon2::default();
     let hash = argon2.hash_password(password.as_bytes(), salt).unwrap();
 
-    match user.await {
-        Ok(user) => {
-            query!(
-                r#"
-                UPDATE users SET verification_code = $1 WHERE id = $2
-                RETURNING id, email, verification_code, created_at
-                "#,
-                hash.to_string(),
-                user.id
-            )
-            .fetch_one(&pool)
-            .await
-            .expect("Failed to update user");
-            send_otp_email(&payload.email, &password).await;
-            return (StatusCode::OK, Json(OtpResponse {}));
-        }
-        Err(_) => {
-            query!(
-                r#"
-                INSERT INTO users (email, verification_code) VALUES ($1, $2)
-                RETURNING id, email, verification_code, created_at
-                "#,
-                payload.email,
-                hash.to_string(),
-            )
-            .fetch_one(&pool)
-            .await
-            .expect("Failed to insert user");
+    // Only the most recently issued code is valid for a user.
+    query!(
+        r#"
+        UPDATE otp_codes SET consumed_at = NOW() WHERE user_id = $1 AND consumed_at IS NULL
+        "#,
+        user.id
+    )
+    .execute(&pool)
+    .await
+    .expect("Failed to invalidate previous codes");
 
-            return (StatusCode::OK, Json(OtpResponse {}));
-        }
-    }
-}
+    let ttl_minutes = otp_ttl_minutes();
+    let code = query!(
+        r#"
+        INSERT INTO otp_codes (user_id, code_hash, expires_at)
+        VALUES ($1, $2, NOW() + make_interval(mins => $3))
+        RETURNING id
+        "#,
+        user.id,
+        hash.to_string(),
+        ttl_minutes
+    )
+    .fetch_one(&pool)
+    .await
+    .expect("Failed to insert verification code");
 
-async fn send_otp_email(to: &str, otp: &str) {
-    let email = Message::builder()
-        .from(
-            var("EMAIL_FROM").expect("EMAIL_FROM must be set").as_str()
-            .parse().unwrap()
-        )
-        .to(to.parse().unwrap())
-        .subject(format!("Your OTP Code: {}", otp))
-        .multipart(
-            MultiPart::alternative()
-                .singlepart(
-                    SinglePart::html(format!("<h3>Hello! 👋</h3><p>Your OTP code for <strong>Notes - Helpedby AI</strong> is: {}</p>", otp))
-                )
-        )
-        .unwrap();
+    let link = magic_link(code.id, ttl_minutes).expect("Failed to sign magic link");
 
-    let creds = Credentials::new(
-        var("EMAIL_USER").expect("EMAIL_USER must be set").as_str().to_string(),
-        var("EMAIL_PASS").expect("EMAIL_PASS must be set").as_str().to_string()
-    );
+    let email = match templates.render("otp", user.locale.as_deref(), context! { code => password, link => link, ttl_minutes => ttl_minutes }) {
+        Ok(email) => email,
+        Err(err) => {
+            eprintln!("Failed to render email: {:#}", err);
+            return (StatusCode::INTERNAL_SERVER_ERROR, Json(OtpResponse { email_id: None, error: Some("Failed to send email".to_string()) }));
+        }
+    };
+    let email_id = outbox
+        .enqueue(Some(user.id), &payload.email, email, Some(ttl_minutes))
+        .await
+        .expect("Failed to queue email");
+    (StatusCode::OK, Json(OtpResponse { email_id: Some(email_id.to_string()), error: None }))
+}
 
-    let mailer = SmtpTransport::starttls_relay(
-        &var("EMAIL_HOST").expect("EMAIL_HOST must be set").as_str().to_string())
-        .unwrap()
-        .credentials(creds)
-        .build();
+/// Minutes an issued code stays valid, `OTP_TTL_MINUTES` (default 10).
+pub fn otp_ttl_minutes() -> i32 {
+    var("OTP_TTL_MINUTES").ok().and_then(|v| v.parse().ok()).unwrap_or(10)
+}
 
-    match mailer.send(&email) {
-        Ok(_) => println!("Email sent successfully!"),
-        Err(e) => eprintln!("Could not send email: {:?}", e),
-    }
+/// Failed guesses allowed before a code is invalidated, `OTP_MAX_ATTEMPTS` (default 5).
+pub fn otp_max_attempts() -> i32 {
+    var("OTP_MAX_ATTEMPTS").ok().and_then(|v| v.parse().ok()).unwrap_or(5)
 }
 
 #[derive(Deserialize)]
@@ -109,4 +118,7 @@ pub struct OtpRequest {
 }
 
 #[derive(Serialize)]
-pub struct OtpResponse {}
+pub struct OtpResponse {
+    email_id: Option<String>,
+    error: Option<String>,
+}
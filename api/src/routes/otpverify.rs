@@ -8,99 +8,208 @@ use axum::{
     Json,
     Extension, http::StatusCode,
 };
-use chrono::{ Duration, Utc };
-use jsonwebtoken::{
-    encode,
-    EncodingKey,
-    Header,
-};
 use serde::{Deserialize, Serialize};
 use sqlx::{PgPool, query};
+use uuid::Uuid;
 
-pub async fn handler(Extension(pool): Extension<PgPool>, Json(payload): Json<OtpVerifyRequest>) -> (StatusCode, Json<OtpVerifyResponse>) {
-    let user = query!(
+use crate::routes::otp::otp_max_attempts;
+use crate::session::{self, ClientInfo};
+use crate::totp::{self, Verification};
+
+pub async fn handler(Extension(pool): Extension<PgPool>, client: ClientInfo, Json(payload): Json<OtpVerifyRequest>) -> (StatusCode, Json<OtpVerifyResponse>) {
+    if payload.verification_code.is_empty() {
+        return error_response("Verification code is required");
+    }
+
+    let code = query!(
         r#"
-        SELECT * FROM users WHERE email = $1
+        SELECT otp_codes.id, otp_codes.code_hash, otp_codes.attempts,
+            otp_codes.expires_at < NOW() AS "expired!"
+        FROM otp_codes
+        JOIN users ON users.id = otp_codes.user_id
+        WHERE users.email = $1 AND otp_codes.consumed_at IS NULL
+        ORDER BY otp_codes.issued_at DESC
+        LIMIT 1
         "#,
         payload.email
     )
-    .fetch_one(&pool);
-
-    match user.await {
-        Ok(user) => {
-            if payload.verification_code.is_empty() {
-                return (StatusCode::BAD_REQUEST, Json(
-                    OtpVerifyResponse { access_token: None }
-                ));
-            }
+    .fetch_optional(&pool)
+    .await
+    .expect("Failed to fetch verification code");
 
-            let code = user.verification_code.clone().unwrap_or_default();
-            let password_hash = PasswordHash::new(&code).expect("invalid password hash");
-            let res = password_hash.verify_password(&[&Argon2::default()], payload.verification_code.as_str());
-            match res {
-                Ok(_) => {
-                    query!(
-                        r#"
-                        UPDATE users SET verification_code = $1 WHERE email = $2
-                        RETURNING id, email, verification_code, created_at
-                        "#,
-                        None::<String>,
-                        payload.email
-                    )
-                    .fetch_one(&pool)
-                    .await
-                    .expect("Failed to update user");
-                    let token = encode_jwt(user.email)
-                        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR).unwrap();
-                    return (StatusCode::OK, Json(
-                        OtpVerifyResponse { access_token: Some(token) }
-                    ));
-                }
-                Err(_) => {
-                    return (StatusCode::BAD_REQUEST, Json(
-                        OtpVerifyResponse { access_token: None }
-                    ));
-                }
-            }
-        }
-        Err(_) => {
-            return (StatusCode::BAD_REQUEST, Json(
-                OtpVerifyResponse { access_token: None }
-            ));
+    let Some(code) = code else {
+        return error_response("Invalid verification code");
+    };
+    if code.expired {
+        return error_response("Verification code has expired");
+    }
+
+    // Claim the attempt before checking the hash so concurrent guesses
+    // cannot exceed the limit.
+    let Some(attempts) = claim_attempt(&pool, code.id).await else {
+        return error_response("Too many attempts, please request a new code");
+    };
+
+    let password_hash = PasswordHash::new(&code.code_hash).expect("invalid password hash");
+    let res = password_hash.verify_password(&[&Argon2::default()], payload.verification_code.as_str());
+    if res.is_err() {
+        if attempts >= otp_max_attempts() {
+            invalidate(&pool, code.id).await;
+            return error_response("Too many attempts, please request a new code");
         }
+        return error_response("Invalid verification code");
     }
+
+    complete_verification(&pool, code.id, payload.totp_code.as_deref(), &client).await
+}
+
+/// Records an attempt against a code, returning the new count, or `None` once
+/// the code is used up.
+pub async fn claim_attempt(pool: &PgPool, code_id: Uuid) -> Option<i32> {
+    let attempt = query!(
+        r#"
+        UPDATE otp_codes SET attempts = attempts + 1
+        WHERE id = $1 AND consumed_at IS NULL AND attempts < $2
+        RETURNING attempts
+        "#,
+        code_id,
+        otp_max_attempts()
+    )
+    .fetch_optional(pool)
+    .await
+    .expect("Failed to update verification code");
+
+    attempt.map(|attempt| attempt.attempts)
 }
 
-pub fn encode_jwt(email: String) -> Result<String, StatusCode> {
-    let secret: String = std::env::var("SECRET").expect("SECRET must be set").as_str().to_string();
-    let now = Utc::now();
-    let expire: chrono::TimeDelta = Duration::hours(120);
-    let exp: usize = (now + expire).timestamp() as usize;
-    let iat: usize = now.timestamp() as usize;
-    let claim = Claims { iat, exp, email };
-
-    encode(
-        &Header::default(),
-        &claim,
-        &EncodingKey::from_secret(secret.as_ref()),
+async fn invalidate(pool: &PgPool, code_id: Uuid) {
+    query!(
+        r#"
+        UPDATE otp_codes SET consumed_at = NOW() WHERE id = $1
+        "#,
+        code_id
     )
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+    .execute(pool)
+    .await
+    .expect("Failed to invalidate verification code");
+}
+
+/// Consumes a verified code and signs the user in. Shared by `/otp-verify`
+/// and the magic link so both paths enforce the same expiry, single use and
+/// authenticator check.
+///
+/// Accounts with an authenticator also need `totp_code`. Without a valid one
+/// the code is left unconsumed so the client can retry with it, and each
+/// wrong guess counts towards the code's attempt limit.
+pub async fn complete_verification(pool: &PgPool, code_id: Uuid, totp_code: Option<&str>, client: &ClientInfo) -> (StatusCode, Json<OtpVerifyResponse>) {
+    let pending = query!(
+        r#"
+        SELECT user_id, attempts FROM otp_codes
+        WHERE id = $1 AND consumed_at IS NULL AND expires_at > NOW()
+        "#,
+        code_id
+    )
+    .fetch_optional(pool)
+    .await
+    .expect("Failed to fetch verification code");
+
+    let Some(pending) = pending else {
+        return error_response("Invalid or expired verification code");
+    };
+
+    let totp_enabled = totp::is_enabled(pool, pending.user_id)
+        .await
+        .expect("Failed to fetch authenticator");
+    if totp_enabled {
+        let Some(totp_code) = totp_code.filter(|code| !code.trim().is_empty()) else {
+            return totp_required_response("Authenticator code is required");
+        };
+        let verification = totp::verify(pool, pending.user_id, totp_code)
+            .await
+            .expect("Failed to verify authenticator code");
+        if let Verification::Locked = verification {
+            return error_response("Too many authenticator attempts, please try again later");
+        }
+        if let Verification::Invalid = verification {
+            if pending.attempts >= otp_max_attempts() {
+                invalidate(pool, code_id).await;
+                return error_response("Too many attempts, please request a new code");
+            }
+            return totp_required_response("Invalid authenticator code");
+        }
+    }
+
+    let consumed = query!(
+        r#"
+        UPDATE otp_codes SET consumed_at = NOW()
+        WHERE id = $1 AND consumed_at IS NULL AND expires_at > NOW()
+        RETURNING user_id
+        "#,
+        code_id
+    )
+    .fetch_optional(pool)
+    .await
+    .expect("Failed to consume verification code");
+
+    let Some(consumed) = consumed else {
+        return error_response("Invalid or expired verification code");
+    };
+
+    let user = query!(
+        r#"
+        UPDATE users SET verified_at = COALESCE(verified_at, NOW()) WHERE id = $1
+        RETURNING id, email
+        "#,
+        consumed.user_id
+    )
+    .fetch_one(pool)
+    .await
+    .expect("Failed to update user");
+
+    sign_in(pool, user.id, user.email, client).await
+}
+
+/// Starts a session and returns the token pair, as every login flow ends the same way.
+pub async fn sign_in(pool: &PgPool, user_id: Uuid, email: String, client: &ClientInfo) -> (StatusCode, Json<OtpVerifyResponse>) {
+    match session::start(pool, user_id, email, client).await {
+        Ok(tokens) => (StatusCode::OK, Json(OtpVerifyResponse {
+            access_token: Some(tokens.access_token),
+            refresh_token: Some(tokens.refresh_token),
+            totp_required: None,
+            error: None,
+        })),
+        Err(status) => (status, Json(OtpVerifyResponse {
+            access_token: None,
+            refresh_token: None,
+            totp_required: None,
+            error: Some("Failed to issue token".to_string()),
+        })),
+    }
+}
+
+pub fn error_response(message: &str) -> (StatusCode, Json<OtpVerifyResponse>) {
+    (StatusCode::BAD_REQUEST, Json(
+        OtpVerifyResponse { access_token: None, refresh_token: None, totp_required: None, error: Some(message.to_string()) }
+    ))
+}
+
+pub fn totp_required_response(message: &str) -> (StatusCode, Json<OtpVerifyResponse>) {
+    (StatusCode::UNAUTHORIZED, Json(
+        OtpVerifyResponse { access_token: None, refresh_token: None, totp_required: Some(true), error: Some(message.to_string()) }
+    ))
 }
 
 #[derive(Deserialize)]
 pub struct OtpVerifyRequest {
     email: String,
     verification_code: String,
+    totp_code: Option<String>,
 }
 
 #[derive(Serialize)]
 pub struct OtpVerifyResponse {
     access_token: Option<String>,
-}
-
-#[derive(Serialize, Deserialize)]
-pub struct Claims {
-    pub exp: usize,
-    pub iat: usize,
-    pub email: String,
+    refresh_token: Option<String>,
+    totp_required: Option<bool>,
+    error: Option<String>,
 }
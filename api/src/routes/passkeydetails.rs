@@ -0,0 +1,43 @@
+use axum::{
+    Json,
+    Extension, http::StatusCode,
+    extract::Path,
+};
+use sqlx::{PgPool, query};
+use uuid::Uuid;
+
+use crate::auth::CurrentUser;
+use crate::routes::passkeys::{Passkey, PasskeyResponse};
+
+pub async fn delete_handler(
+    Path(passkey_id): Path<String>,
+    Extension(pool): Extension<PgPool>,
+    Extension(auth_user): Extension<CurrentUser>
+) -> (StatusCode, Json<PasskeyResponse>) {
+    let Ok(passkey_id) = Uuid::parse_str(&passkey_id) else {
+        return (StatusCode::NOT_FOUND, Json(PasskeyResponse { passkey: None, error: Some("Passkey not found".to_string()) }));
+    };
+
+    let passkey = query!(
+        r#"
+        DELETE FROM passkeys WHERE id = $1 AND user_id = $2
+        RETURNING id, name, created_at, last_used_at
+        "#,
+        passkey_id,
+        Uuid::parse_str(&auth_user.id).unwrap()
+    )
+    .fetch_one(&pool);
+
+    match passkey.await {
+        Ok(passkey) => {
+            let passkey = Passkey {
+                id: passkey.id.to_string(),
+                name: passkey.name,
+                created_at: passkey.created_at.to_string(),
+                last_used_at: passkey.last_used_at.map(|t| t.to_string()),
+            };
+            (StatusCode::OK, Json(PasskeyResponse { passkey: Some(passkey), error: None }))
+        }
+        Err(_) => (StatusCode::NOT_FOUND, Json(PasskeyResponse { passkey: None, error: Some("Passkey not found".to_string()) })),
+    }
+}
@@ -0,0 +1,154 @@
+use axum::{
+    Json,
+    Extension, http::StatusCode,
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use sqlx::{PgPool, query};
+use std::sync::OnceLock;
+use uuid::Uuid;
+use webauthn_rs::prelude::{PasskeyAuthentication, PublicKeyCredential, RequestChallengeResponse, Webauthn};
+
+use crate::passkey::{credential_id, save_ceremony, take_ceremony, user_passkeys};
+use crate::routes::otpverify::{error_response, sign_in, OtpVerifyResponse};
+use crate::session::ClientInfo;
+
+const KIND: &str = "authentication";
+
+/// Key the credential ids of decoy challenges are derived from.
+static DECOY_KEY: OnceLock<[u8; 32]> = OnceLock::new();
+
+/// Starts a passkey sign-in for an email. Emails without a verified account
+/// or without passkeys get a decoy challenge that no authenticator can answer,
+/// so the response doesn't reveal which accounts exist or use passkeys.
+pub async fn start_handler(
+    Extension(pool): Extension<PgPool>,
+    Extension(webauthn): Extension<Webauthn>,
+    Json(payload): Json<LoginStartRequest>
+) -> (StatusCode, Json<LoginStartResponse>) {
+    let user = query!(
+        r#"
+        SELECT id FROM users WHERE email = $1 AND verified_at IS NOT NULL
+        "#,
+        payload.email
+    )
+    .fetch_optional(&pool)
+    .await
+    .expect("Failed to fetch user");
+
+    let passkeys = match user {
+        Some(ref user) => user_passkeys(&pool, user.id).await.expect("Failed to fetch passkeys"),
+        None => vec![],
+    };
+    let (Some(user), false) = (user, passkeys.is_empty()) else {
+        return match decoy_options(&webauthn, &payload.email) {
+            Some(options) => (StatusCode::OK, Json(LoginStartResponse { ceremony_id: Some(Uuid::new_v4().to_string()), options: Some(options), error: None })),
+            None => (StatusCode::INTERNAL_SERVER_ERROR, Json(LoginStartResponse { ceremony_id: None, options: None, error: Some("Failed to start sign-in".to_string()) })),
+        };
+    };
+
+    let passkeys: Vec<_> = passkeys.into_iter().map(|(_, passkey)| passkey).collect();
+    let (options, state) = match webauthn.start_passkey_authentication(&passkeys) {
+        Ok(result) => result,
+        Err(err) => return (StatusCode::BAD_REQUEST, Json(LoginStartResponse { ceremony_id: None, options: None, error: Some(err.to_string()) })),
+    };
+
+    let ceremony_id = save_ceremony(&pool, user.id, KIND, &state)
+        .await
+        .expect("Failed to save authentication");
+
+    (StatusCode::OK, Json(LoginStartResponse {
+        ceremony_id: Some(ceremony_id.to_string()),
+        options: Some(options),
+        error: None,
+    }))
+}
+
+pub async fn finish_handler(
+    Extension(pool): Extension<PgPool>,
+    Extension(webauthn): Extension<Webauthn>,
+    client: ClientInfo,
+    Json(payload): Json<LoginFinishRequest>
+) -> (StatusCode, Json<OtpVerifyResponse>) {
+    let Ok(ceremony_id) = Uuid::parse_str(&payload.ceremony_id) else {
+        return error_response("Sign-in not found or expired");
+    };
+    let ceremony = take_ceremony::<PasskeyAuthentication>(&pool, ceremony_id, KIND)
+        .await
+        .expect("Failed to fetch authentication");
+    let Some((user_id, state)) = ceremony else {
+        return error_response("Sign-in not found or expired");
+    };
+
+    let result = match webauthn.finish_passkey_authentication(&payload.credential, &state) {
+        Ok(result) => result,
+        Err(_) => return error_response("Passkey verification failed"),
+    };
+
+    // Persist the new signature counter so cloned authenticators are detected.
+    let used_id = credential_id(result.cred_id());
+    let passkeys = user_passkeys(&pool, user_id).await.expect("Failed to fetch passkeys");
+    for (id, mut passkey) in passkeys {
+        if credential_id(passkey.cred_id()) != used_id {
+            continue;
+        }
+        passkey.update_credential(&result);
+        query!(
+            r#"
+            UPDATE passkeys SET passkey = $1, last_used_at = NOW() WHERE id = $2
+            "#,
+            serde_json::to_value(&passkey).expect("Failed to serialize passkey"),
+            id
+        )
+        .execute(&pool)
+        .await
+        .expect("Failed to update passkey");
+    }
+
+    let user = query!(
+        r#"
+        SELECT id, email FROM users WHERE id = $1
+        "#,
+        user_id
+    )
+    .fetch_one(&pool)
+    .await
+    .expect("Failed to fetch user");
+
+    sign_in(&pool, user.id, user.email, &client).await
+}
+
+/// Options shaped like a real challenge, allowing a single credential whose
+/// id is derived from the email, so asking twice gives the same id as it
+/// would for a real account.
+fn decoy_options(webauthn: &Webauthn, email: &str) -> Option<RequestChallengeResponse> {
+    let (options, _) = webauthn.start_discoverable_authentication().ok()?;
+    let key = DECOY_KEY.get_or_init(rand::random);
+    let id = Sha256::new().chain_update(key).chain_update(email.trim().to_lowercase()).finalize();
+
+    let mut options = serde_json::to_value(options).ok()?;
+    let public_key = options.get_mut("publicKey")?.as_object_mut()?;
+    public_key.remove("extensions");
+    public_key.insert("allowCredentials".to_string(), json!([{ "type": "public-key", "id": URL_SAFE_NO_PAD.encode(&id[..16]) }]));
+    serde_json::from_value(options).ok()
+}
+
+#[derive(Deserialize)]
+pub struct LoginStartRequest {
+    email: String,
+}
+
+#[derive(Deserialize)]
+pub struct LoginFinishRequest {
+    ceremony_id: String,
+    credential: PublicKeyCredential,
+}
+
+#[derive(Serialize)]
+pub struct LoginStartResponse {
+    ceremony_id: Option<String>,
+    options: Option<RequestChallengeResponse>,
+    error: Option<String>,
+}
@@ -0,0 +1,119 @@
+use axum::{
+    Json,
+    Extension, http::StatusCode,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, query};
+use uuid::Uuid;
+use webauthn_rs::prelude::{CreationChallengeResponse, PasskeyRegistration, RegisterPublicKeyCredential, Webauthn};
+
+use crate::auth::CurrentUser;
+use crate::passkey::{credential_id, save_ceremony, take_ceremony, user_passkeys};
+use crate::routes::passkeys::{Passkey, PasskeyResponse};
+
+const KIND: &str = "registration";
+
+pub async fn start_handler(
+    Extension(pool): Extension<PgPool>,
+    Extension(webauthn): Extension<Webauthn>,
+    Extension(auth_user): Extension<CurrentUser>
+) -> (StatusCode, Json<RegisterStartResponse>) {
+    let user_id = Uuid::parse_str(&auth_user.id).unwrap();
+    let existing = user_passkeys(&pool, user_id)
+        .await
+        .expect("Failed to fetch passkeys")
+        .into_iter()
+        .map(|(_, passkey)| passkey.cred_id().clone())
+        .collect::<Vec<_>>();
+
+    let (options, state) = match webauthn.start_passkey_registration(user_id, &auth_user.email, &auth_user.email, Some(existing)) {
+        Ok(result) => result,
+        Err(err) => return (StatusCode::BAD_REQUEST, Json(RegisterStartResponse { ceremony_id: None, options: None, error: Some(err.to_string()) })),
+    };
+
+    let ceremony_id = save_ceremony(&pool, user_id, KIND, &state)
+        .await
+        .expect("Failed to save registration");
+
+    (StatusCode::OK, Json(RegisterStartResponse {
+        ceremony_id: Some(ceremony_id.to_string()),
+        options: Some(options),
+        error: None,
+    }))
+}
+
+pub async fn finish_handler(
+    Extension(pool): Extension<PgPool>,
+    Extension(webauthn): Extension<Webauthn>,
+    Extension(auth_user): Extension<CurrentUser>,
+    Json(payload): Json<RegisterFinishRequest>
+) -> (StatusCode, Json<PasskeyResponse>) {
+    let name = payload.name.unwrap_or_else(|| "Passkey".to_string());
+    if name.trim().is_empty() || name.len() > 100 {
+        return error_response(StatusCode::BAD_REQUEST, "Name must be between 1 and 100 characters");
+    }
+
+    let Ok(ceremony_id) = Uuid::parse_str(&payload.ceremony_id) else {
+        return error_response(StatusCode::BAD_REQUEST, "Registration not found or expired");
+    };
+    let ceremony = take_ceremony::<PasskeyRegistration>(&pool, ceremony_id, KIND)
+        .await
+        .expect("Failed to fetch registration");
+    let state = match ceremony {
+        Some((user_id, state)) if user_id.to_string() == auth_user.id => state,
+        _ => return error_response(StatusCode::BAD_REQUEST, "Registration not found or expired"),
+    };
+
+    let passkey = match webauthn.finish_passkey_registration(&payload.credential, &state) {
+        Ok(passkey) => passkey,
+        Err(err) => return error_response(StatusCode::BAD_REQUEST, &err.to_string()),
+    };
+
+    let saved = query!(
+        r#"
+        INSERT INTO passkeys (user_id, name, credential_id, passkey)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT (credential_id) DO NOTHING
+        RETURNING id, name, created_at, last_used_at
+        "#,
+        Uuid::parse_str(&auth_user.id).unwrap(),
+        name.trim(),
+        credential_id(passkey.cred_id()),
+        serde_json::to_value(&passkey).expect("Failed to serialize passkey")
+    )
+    .fetch_optional(&pool)
+    .await
+    .expect("Failed to save passkey");
+
+    match saved {
+        Some(saved) => (StatusCode::OK, Json(PasskeyResponse {
+            passkey: Some(Passkey {
+                id: saved.id.to_string(),
+                name: saved.name,
+                created_at: saved.created_at.to_string(),
+                last_used_at: saved.last_used_at.map(|t| t.to_string()),
+            }),
+            error: None,
+        })),
+        None => error_response(StatusCode::CONFLICT, "Passkey is already registered"),
+    }
+}
+
+fn error_response(status: StatusCode, message: &str) -> (StatusCode, Json<PasskeyResponse>) {
+    (status, Json(PasskeyResponse { passkey: None, error: Some(message.to_string()) }))
+}
+
+#[derive(Deserialize)]
+pub struct RegisterFinishRequest {
+    ceremony_id: String,
+    name: Option<String>,
+    credential: RegisterPublicKeyCredential,
+}
+
+#[derive(Serialize)]
+pub struct RegisterStartResponse {
+    ceremony_id: Option<String>,
+    options: Option<CreationChallengeResponse>,
+    error: Option<String>,
+}
+
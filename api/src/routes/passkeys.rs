@@ -0,0 +1,50 @@
+use axum::{
+    Json,
+    Extension, http::StatusCode,
+};
+use serde::Serialize;
+use sqlx::{PgPool, query};
+use uuid::Uuid;
+
+use crate::auth::CurrentUser;
+
+pub async fn get_handler(Extension(pool): Extension<PgPool>, Extension(auth_user): Extension<CurrentUser>) -> (StatusCode, Json<PasskeysResponse>) {
+    let passkeys = query!(
+        r#"
+        SELECT id, name, created_at, last_used_at FROM passkeys WHERE user_id = $1 ORDER BY created_at DESC
+        "#,
+        Uuid::parse_str(&auth_user.id).unwrap()
+    )
+        .fetch_all(&pool)
+        .await
+        .expect("Failed to fetch passkeys");
+
+    let passkeys: Vec<Passkey> = passkeys.into_iter().map(|passkey| Passkey {
+        id: passkey.id.to_string(),
+        name: passkey.name,
+        created_at: passkey.created_at.to_string(),
+        last_used_at: passkey.last_used_at.map(|t| t.to_string()),
+    }).collect();
+
+    (StatusCode::OK, Json(PasskeysResponse { passkeys, error: None }))
+}
+
+#[derive(Serialize)]
+pub struct PasskeysResponse {
+    passkeys: Vec<Passkey>,
+    error: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct PasskeyResponse {
+    pub passkey: Option<Passkey>,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct Passkey {
+    pub id: String,
+    pub name: String,
+    pub created_at: String,
+    pub last_used_at: Option<String>,
+}
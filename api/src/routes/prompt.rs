@@ -1,141 +1,202 @@
 use axum::{
-    http::StatusCode,
     Json,
+    Extension, http::StatusCode,
+    response::{IntoResponse, Response, sse::{Event, KeepAlive, Sse}},
 };
+use futures_util::{stream::{self, BoxStream}, StreamExt};
 use serde::{Deserialize, Serialize};
-
-pub async fn handler(Json(payload): Json<PromptRequest>) -> (StatusCode, Json<PromptResponse>) {
-    let prompt = payload.prompt;
-    let context = payload.context.unwrap_or_default();
-
-    let api_key = std::env::var("ROUTER_AI_API_KEY").expect("ROUTER_AI_API_KEY must be set");
-    let body = AIRequest {
-        model: default_model(),
-        messages: vec![
-            AIMessage {
-                role: "system".to_string(),
-                content: format!(concat!(
-                    "You are a helpful writing assistant. Please help the user to replace the selected text.\n\n",
-                    "Your task is: {}\n\n",
-                    "Make sure to provide only exact 1 option as a response."
-                ), prompt),
-            },
-            AIMessage {
-                role: "user".to_string(),
-                content: format!("The selected text is: {}", context),
-            },
-        ],
-        stream: false,
-        reasoning: AIReasoning {
-            effort: "none".to_string(),
-        },
+use serde_json::json;
+use sqlx::PgPool;
+use std::convert::Infallible;
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+use crate::actions;
+use crate::ai::{AiError, Chat, Chunk, Message, Provider, Usage};
+use crate::auth::CurrentUser;
+use crate::style;
+use crate::usage;
+
+/// Runs an AI action, or a free-form `prompt`, on the selected text for the
+/// signed-in user. Each request counts against the user's daily and monthly
+/// AI quotas, along with the tokens the provider reports for it.
+pub async fn handler(Extension(pool): Extension<PgPool>, Extension(provider): Extension<Provider>, Extension(auth_user): Extension<CurrentUser>, Json(payload): Json<PromptRequest>) -> (StatusCode, Json<PromptResponse>) {
+    let user_id = Uuid::parse_str(&auth_user.id).unwrap();
+    let chat = match chat(&pool, &provider, user_id, &payload).await {
+        Ok(chat) => chat,
+        Err((status, error)) => return (status, Json(PromptResponse::Error { error })),
     };
 
-    let client = reqwest::Client::new();
-    let resp = client.post("https://router.helpedby.ai/v1/chat/completions")
-        .json(&body)
-        .header("Authorization", format!("Bearer {}", api_key))
-        .send()
-        .await;
-    match resp {
-        Ok(resp) => {
-            let status = resp.status();
-            let text = resp.text().await.unwrap_or_default();
-
-            if !status.is_success() {
-                if let Ok(err) = serde_json::from_str::<AIResponseError>(&text) {
-                    return (StatusCode::BAD_REQUEST, Json(PromptResponse::Error { error: err.error.message }));
-                }
+    let usage_id = match usage::reserve(&pool, user_id, &chat.model)
+        .await
+        .expect("Failed to check AI usage")
+    {
+        Ok(usage_id) => usage_id,
+        Err(error) => return (StatusCode::TOO_MANY_REQUESTS, Json(PromptResponse::Error { error })),
+    };
 
-                return (StatusCode::BAD_REQUEST, Json(PromptResponse::Error { error: text }));
+    match provider.complete(chat).await {
+        Ok(completion) => {
+            if let Some(tokens) = completion.usage {
+                usage::record(&pool, usage_id, tokens.prompt_tokens, tokens.completion_tokens)
+                    .await
+                    .expect("Failed to record AI usage");
             }
 
-            match serde_json::from_str::<AIResponse>(&text) {
-                Ok(json) => {
-                    let result = json
-                        .choices
-                        .first()
-                        .and_then(|choice| choice.message.content.as_deref())
-                        .unwrap_or_default();
-
-                    (StatusCode::OK, Json(PromptResponse::Result { result: result.to_string() }))
-                }
-                Err(err) => (
-                    StatusCode::BAD_REQUEST,
-                    Json(PromptResponse::Error {
-                        error: format!("Failed to decode AI response: {}", err),
-                    }),
-                ),
-            }
-        }
-        Err(err) => {
-            return (StatusCode::INTERNAL_SERVER_ERROR, Json(PromptResponse::Error { error: err.to_string() }));
+            (StatusCode::OK, Json(PromptResponse::Result { result: completion.content }))
         }
+        Err(err) => (err.status(), Json(PromptResponse::Error { error: err.to_string() })),
     }
 }
 
-#[derive(Deserialize)]
-pub struct PromptRequest {
-    context: Option<String>,
-    prompt: String,
-}
+/// Same as `handler`, but streams the completion as Server-Sent Events:
+/// `delta` events with `{content}` as the provider produces text, then a
+/// single `done` event with `{result, usage}` or an `error` event with
+/// `{error}`. Quota and provider errors that happen before the first token
+/// are returned as plain JSON with an error status instead.
+///
+/// If the client goes away the upstream request is dropped, so an abandoned
+/// rewrite stops costing tokens.
+pub async fn stream_handler(Extension(pool): Extension<PgPool>, Extension(provider): Extension<Provider>, Extension(auth_user): Extension<CurrentUser>, Json(payload): Json<PromptRequest>) -> Response {
+    let user_id = Uuid::parse_str(&auth_user.id).unwrap();
+    let chat = match chat(&pool, &provider, user_id, &payload).await {
+        Ok(chat) => chat,
+        Err((status, error)) => return (status, Json(PromptResponse::Error { error })).into_response(),
+    };
 
-#[derive(Serialize)]
-#[serde(untagged)]
-pub enum PromptResponse {
-    Result { result: String },
-    Error { error: String },
-}
+    let usage_id = match usage::reserve(&pool, user_id, &chat.model)
+        .await
+        .expect("Failed to check AI usage")
+    {
+        Ok(usage_id) => usage_id,
+        Err(error) => return (StatusCode::TOO_MANY_REQUESTS, Json(PromptResponse::Error { error })).into_response(),
+    };
 
-#[derive(Serialize)]
-#[serde(rename_all = "camelCase")]
-struct AIRequest {
-    model: String,
-    messages: Vec<AIMessage>,
-    stream: bool,
-    reasoning: AIReasoning,
-}
+    let prompt_chars = chat.messages.iter().map(|message| message.content.chars().count()).sum();
+    let upstream = match provider.stream(chat).await {
+        Ok(upstream) => upstream,
+        Err(err) => return (err.status(), Json(PromptResponse::Error { error: err.to_string() })).into_response(),
+    };
 
-fn default_model() -> String {
-    "basic.free".to_string()
-}
+    let (tx, rx) = mpsc::channel(32);
+    tokio::spawn(forward(pool, usage_id, upstream, prompt_chars, tx));
 
-#[derive(Serialize)]
-struct AIMessage {
-    role: String,
-    content: String,
+    let events = stream::unfold(rx, |mut rx| async move {
+        rx.recv().await.map(|event| (Ok::<_, Infallible>(event), rx))
+    });
+    Sse::new(events).keep_alive(KeepAlive::default()).into_response()
 }
 
-#[derive(Serialize)]
-#[serde(rename_all = "camelCase")]
-struct AIReasoning {
-    effort: String,
-}
+/// Relays the provider's stream to the client until it ends, fails or the
+/// client disconnects, then records the request's usage.
+async fn forward(pool: PgPool, usage_id: Uuid, mut upstream: BoxStream<'static, Result<Chunk, AiError>>, prompt_chars: usize, tx: mpsc::Sender<Event>) {
+    let mut result = String::new();
+    let mut tokens = None;
+    let mut error = None;
+    let mut cancelled = false;
+
+    loop {
+        let chunk = tokio::select! {
+            _ = tx.closed() => {
+                cancelled = true;
+                break;
+            }
+            chunk = upstream.next() => chunk,
+        };
+        match chunk {
+            Some(Ok(Chunk::Content(content))) => {
+                result.push_str(&content);
+                if tx.send(Event::default().event("delta").data(json!({ "content": content }).to_string())).await.is_err() {
+                    cancelled = true;
+                    break;
+                }
+            }
+            Some(Ok(Chunk::Usage(usage))) => tokens = Some(usage),
+            Some(Err(err)) => {
+                error = Some(err.to_string());
+                break;
+            }
+            None => break,
+        }
+    }
+    drop(upstream);
+
+    // Providers only report usage at the end of a stream, so a cancelled or
+    // failed one is counted at roughly four characters per token.
+    let tokens = tokens.unwrap_or(Usage {
+        prompt_tokens: usage::estimate_tokens(prompt_chars),
+        completion_tokens: usage::estimate_tokens(result.chars().count()),
+    });
+    if let Err(err) = usage::record(&pool, usage_id, tokens.prompt_tokens, tokens.completion_tokens).await {
+        eprintln!("Failed to record AI usage: {}", err);
+    }
 
-#[derive(Deserialize)]
-#[serde(rename_all = "camelCase")]
-struct AIResponse {
-    choices: Vec<AIChoice>,
+    if cancelled {
+        return;
+    }
+    let event = match error {
+        Some(error) => Event::default().event("error").data(json!({ "error": error }).to_string()),
+        None => Event::default().event("done").data(json!({
+            "result": result,
+            "usage": {
+                "prompt_tokens": tokens.prompt_tokens,
+                "completion_tokens": tokens.completion_tokens,
+            },
+        }).to_string()),
+    };
+    let _ = tx.send(event).await;
 }
 
-#[derive(Deserialize)]
-struct AIChoice {
-    message: AIResponseMessage,
-}
+/// Builds the request for the action the client picked, or for a free-form
+/// `prompt` when it didn't name one.
+async fn chat(pool: &PgPool, provider: &Provider, user_id: Uuid, payload: &PromptRequest) -> Result<Chat, (StatusCode, String)> {
+    let context = Message::user(format!("The selected text is: {}", payload.context.as_deref().unwrap_or_default()));
+    let profile = style::profile(pool, user_id).await.expect("Failed to fetch writing style");
+
+    let Some(action_id) = &payload.action else {
+        let Some(prompt) = payload.prompt.as_deref().filter(|prompt| !prompt.trim().is_empty()) else {
+            return Err((StatusCode::BAD_REQUEST, "Either action or prompt is required".to_string()));
+        };
+        let system = format!(concat!(
+            "You are a helpful writing assistant. Please help the user to replace the selected text.\n\n",
+            "Your task is: {}\n\n",
+            "Make sure to provide only exact 1 option as a response."
+        ), prompt);
+        return Ok(provider.chat(vec![Message::system(style::apply(system, profile.as_deref())), context]));
+    };
 
-#[derive(Deserialize)]
-struct AIResponseMessage {
-    content: Option<String>,
+    let action = actions::find(pool, user_id, action_id)
+        .await
+        .expect("Failed to fetch AI action")
+        .ok_or((StatusCode::NOT_FOUND, "Action not found".to_string()))?;
+    let system = action
+        .system_message(payload.input.as_deref())
+        .map_err(|error| (StatusCode::BAD_REQUEST, error))?;
+
+    let mut chat = provider.chat(vec![Message::system(style::apply(system, profile.as_deref())), context]);
+    // Actions saved before the model was taken off `AI_ALLOWED_MODELS` fall
+    // back to the default.
+    if let Some(model) = action.model.filter(|model| provider.allows_model(model)) {
+        chat.model = model;
+    }
+    chat.temperature = action.temperature;
+    Ok(chat)
 }
 
 #[derive(Deserialize)]
-#[serde(rename_all = "camelCase")]
-struct AIResponseError {
-    error: AIError,
+pub struct PromptRequest {
+    context: Option<String>,
+    /// Id of an action from `GET /ai/actions`.
+    action: Option<String>,
+    /// Value for actions that take one, such as the tone or language.
+    input: Option<String>,
+    /// Free-form instructions, used when no action is given.
+    prompt: Option<String>,
 }
 
-#[derive(Deserialize)]
-#[serde(rename_all = "camelCase")]
-struct AIError {
-    message: String,
+#[derive(Serialize)]
+#[serde(untagged)]
+pub enum PromptResponse {
+    Result { result: String },
+    Error { error: String },
 }
@@ -0,0 +1,61 @@
+use axum::{
+    Json,
+    Extension, http::StatusCode,
+    extract::Path,
+};
+use serde::Serialize;
+use sqlx::{PgPool, query};
+use uuid::Uuid;
+
+use crate::auth::CurrentUser;
+use crate::routes::revisions::Revision;
+
+pub async fn get_handler(
+    Path((doc_id, revision_id)): Path<(String, String)>,
+    Extension(pool): Extension<PgPool>,
+    Extension(auth_user): Extension<CurrentUser>
+) -> (StatusCode, Json<RevisionResponse>) {
+    let (Ok(doc_id), Ok(revision_id)) = (Uuid::parse_str(&doc_id), Uuid::parse_str(&revision_id)) else {
+        return (StatusCode::NOT_FOUND, Json(RevisionResponse { revision: None, error: Some("Revision not found".to_string()) }));
+    };
+
+    let revision = query!(
+        r#"
+        SELECT doc_revisions.id, doc_revisions.doc_id, doc_revisions.author_id, doc_revisions.title,
+            doc_revisions.content_text, doc_revisions.content_json, doc_revisions.content_html,
+            doc_revisions.created_at, doc_revisions.updated_at
+        FROM doc_revisions JOIN docs ON docs.id = doc_revisions.doc_id
+        WHERE doc_revisions.id = $1 AND doc_revisions.doc_id = $2 AND docs.user_id = $3
+        "#,
+        revision_id,
+        doc_id,
+        Uuid::parse_str(&auth_user.id).unwrap()
+    )
+    .fetch_optional(&pool)
+    .await
+    .expect("Failed to fetch revision");
+    let Some(revision) = revision else {
+        return (StatusCode::NOT_FOUND, Json(RevisionResponse { revision: None, error: Some("Revision not found".to_string()) }));
+    };
+
+    (StatusCode::OK, Json(RevisionResponse {
+        revision: Some(Revision {
+            id: revision.id.to_string(),
+            doc_id: revision.doc_id.to_string(),
+            author_id: revision.author_id.map(|id| id.to_string()),
+            title: revision.title,
+            content_text: Some(revision.content_text),
+            content_json: Some(revision.content_json),
+            content_html: Some(revision.content_html),
+            created_at: revision.created_at.to_string(),
+            updated_at: revision.updated_at.to_string(),
+        }),
+        error: None,
+    }))
+}
+
+#[derive(Serialize)]
+pub struct RevisionResponse {
+    revision: Option<Revision>,
+    error: Option<String>,
+}
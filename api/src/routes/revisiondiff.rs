@@ -0,0 +1,101 @@
+use axum::{
+    Json,
+    Extension, http::StatusCode,
+    extract::{Path, Query},
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, query};
+use uuid::Uuid;
+
+use crate::auth::CurrentUser;
+use crate::diff::{self, BlockChange};
+
+/// Compares revision `from` of a document with revision `to`, or with the
+/// latest revision when `to` is left out.
+pub async fn get_handler(
+    Path(doc_id): Path<String>,
+    Extension(pool): Extension<PgPool>,
+    Extension(auth_user): Extension<CurrentUser>,
+    Query(params): Query<Params>
+) -> (StatusCode, Json<DiffResponse>) {
+    let Ok(doc_id) = Uuid::parse_str(&doc_id) else {
+        return (StatusCode::NOT_FOUND, Json(DiffResponse { diff: None, error: Some("Document not found".to_string()) }));
+    };
+    let Ok(from) = Uuid::parse_str(&params.from) else {
+        return (StatusCode::NOT_FOUND, Json(DiffResponse { diff: None, error: Some("Revision not found".to_string()) }));
+    };
+    let to = match params.to.as_deref().map(Uuid::parse_str) {
+        Some(Ok(to)) => Some(to),
+        Some(Err(_)) => return (StatusCode::NOT_FOUND, Json(DiffResponse { diff: None, error: Some("Revision not found".to_string()) })),
+        None => None,
+    };
+
+    let revisions = query!(
+        r#"
+        SELECT doc_revisions.id, doc_revisions.title, doc_revisions.content_text, doc_revisions.content_json, doc_revisions.created_at
+        FROM doc_revisions JOIN docs ON docs.id = doc_revisions.doc_id
+        WHERE doc_revisions.doc_id = $1 AND docs.user_id = $2
+            AND (doc_revisions.id = $3 OR doc_revisions.id = $4
+                OR ($4::uuid IS NULL AND doc_revisions.id = (
+                    SELECT id FROM doc_revisions WHERE doc_id = $1 ORDER BY created_at DESC LIMIT 1
+                )))
+        "#,
+        doc_id,
+        Uuid::parse_str(&auth_user.id).unwrap(),
+        from,
+        to
+    )
+    .fetch_all(&pool)
+    .await
+    .expect("Failed to fetch revisions");
+
+    let old = revisions.iter().find(|revision| revision.id == from);
+    let new = match to {
+        Some(to) => revisions.iter().find(|revision| revision.id == to),
+        None => revisions.iter().max_by_key(|revision| revision.created_at),
+    };
+    let (Some(old), Some(new)) = (old, new) else {
+        return (StatusCode::NOT_FOUND, Json(DiffResponse { diff: None, error: Some("Revision not found".to_string()) }));
+    };
+
+    (StatusCode::OK, Json(DiffResponse {
+        diff: Some(Diff {
+            from: old.id.to_string(),
+            to: new.id.to_string(),
+            old_title: old.title.clone(),
+            new_title: new.title.clone(),
+            blocks: diff::blocks(&old.content_json, &new.content_json),
+            text: diff::text(
+                &old.content_text,
+                &new.content_text,
+                &format!("{} ({})", old.title, old.created_at),
+                &format!("{} ({})", new.title, new.created_at),
+            ),
+        }),
+        error: None,
+    }))
+}
+
+#[derive(Deserialize)]
+pub struct Params {
+    from: String,
+    to: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct DiffResponse {
+    diff: Option<Diff>,
+    error: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct Diff {
+    from: String,
+    to: String,
+    old_title: String,
+    new_title: String,
+    /// Changes to the blocks of `content_json`.
+    blocks: Vec<BlockChange>,
+    /// Unified diff of `content_text`.
+    text: String,
+}
@@ -0,0 +1,65 @@
+use axum::{
+    Json,
+    Extension, http::StatusCode,
+    extract::Path,
+};
+use sqlx::{PgPool, query};
+use uuid::Uuid;
+
+use crate::ai::Provider;
+use crate::auth::CurrentUser;
+use crate::embeddings;
+use crate::revisions;
+use crate::routes::docdetails::{Doc, DocResponse};
+
+/// Makes a revision's content the document's current content. The restore
+/// is recorded as a revision of its own, so it can be undone the same way.
+pub async fn post_handler(
+    Path((doc_id, revision_id)): Path<(String, String)>,
+    Extension(pool): Extension<PgPool>,
+    Extension(provider): Extension<Provider>,
+    Extension(auth_user): Extension<CurrentUser>
+) -> (StatusCode, Json<DocResponse>) {
+    let (Ok(doc_id), Ok(revision_id)) = (Uuid::parse_str(&doc_id), Uuid::parse_str(&revision_id)) else {
+        return (StatusCode::NOT_FOUND, Json(DocResponse { doc: None, error: Some("Revision not found".to_string()) }));
+    };
+    let user_id = Uuid::parse_str(&auth_user.id).unwrap();
+
+    let mut tx = pool.begin().await.expect("Failed to start transaction");
+    let doc = query!(
+        r#"
+        UPDATE docs
+        SET title = doc_revisions.title, content_text = doc_revisions.content_text,
+            content_json = doc_revisions.content_json, content_html = doc_revisions.content_html, updated_at = NOW()
+        FROM doc_revisions
+        WHERE docs.id = $1 AND docs.user_id = $2 AND doc_revisions.id = $3 AND doc_revisions.doc_id = docs.id
+        RETURNING docs.id, docs.user_id, docs.title, docs.content_text, docs.content_json, docs.content_html, docs.created_at, docs.updated_at
+        "#,
+        doc_id,
+        user_id,
+        revision_id
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .expect("Failed to restore revision");
+    let Some(doc) = doc else {
+        return (StatusCode::NOT_FOUND, Json(DocResponse { doc: None, error: Some("Revision not found".to_string()) }));
+    };
+    revisions::record(&mut tx, doc.id, user_id, false).await.expect("Failed to record revision");
+    tx.commit().await.expect("Failed to commit transaction");
+    embeddings::spawn_index(pool, provider, user_id, doc.id);
+
+    (StatusCode::OK, Json(DocResponse {
+        doc: Some(Doc {
+            id: doc.id.to_string(),
+            user_id: doc.user_id.to_string(),
+            title: doc.title,
+            content_text: doc.content_text,
+            content_json: doc.content_json,
+            content_html: doc.content_html,
+            created_at: doc.created_at.expect("Failed to parse created_at").to_string(),
+            updated_at: doc.updated_at.expect("Failed to parse updated_at").to_string(),
+        }),
+        error: None,
+    }))
+}
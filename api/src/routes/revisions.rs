@@ -0,0 +1,83 @@
+use axum::{
+    Json,
+    Extension, http::StatusCode,
+    extract::Path,
+};
+use serde::Serialize;
+use serde_json::Value;
+use sqlx::{PgPool, query};
+use uuid::Uuid;
+
+use crate::auth::CurrentUser;
+
+/// Lists a document's revisions, newest first, without their content.
+pub async fn get_handler(
+    Path(doc_id): Path<String>,
+    Extension(pool): Extension<PgPool>,
+    Extension(auth_user): Extension<CurrentUser>
+) -> (StatusCode, Json<RevisionsResponse>) {
+    let Ok(doc_id) = Uuid::parse_str(&doc_id) else {
+        return (StatusCode::NOT_FOUND, Json(RevisionsResponse { revisions: Vec::new(), error: Some("Document not found".to_string()) }));
+    };
+
+    let doc = query!(
+        r#"
+        SELECT id FROM docs WHERE id = $1 AND user_id = $2
+        "#,
+        doc_id,
+        Uuid::parse_str(&auth_user.id).unwrap()
+    )
+    .fetch_optional(&pool)
+    .await
+    .expect("Failed to fetch doc");
+    if doc.is_none() {
+        return (StatusCode::NOT_FOUND, Json(RevisionsResponse { revisions: Vec::new(), error: Some("Document not found".to_string()) }));
+    }
+
+    let revisions = query!(
+        r#"
+        SELECT id, doc_id, author_id, title, created_at, updated_at FROM doc_revisions
+        WHERE doc_id = $1
+        ORDER BY created_at DESC
+        "#,
+        doc_id
+    )
+    .fetch_all(&pool)
+    .await
+    .expect("Failed to fetch revisions");
+
+    let revisions = revisions.into_iter().map(|revision| Revision {
+        id: revision.id.to_string(),
+        doc_id: revision.doc_id.to_string(),
+        author_id: revision.author_id.map(|id| id.to_string()),
+        title: revision.title,
+        content_text: None,
+        content_json: None,
+        content_html: None,
+        created_at: revision.created_at.to_string(),
+        updated_at: revision.updated_at.to_string(),
+    }).collect();
+
+    (StatusCode::OK, Json(RevisionsResponse { revisions, error: None }))
+}
+
+#[derive(Serialize)]
+pub struct RevisionsResponse {
+    revisions: Vec<Revision>,
+    error: Option<String>,
+}
+
+/// A saved version of a document. `updated_at` moves on while later saves
+/// are folded into it.
+#[derive(Serialize)]
+pub struct Revision {
+    pub id: String,
+    pub doc_id: String,
+    pub author_id: Option<String>,
+    pub title: String,
+    pub content_text: Option<String>,
+    pub content_json: Option<Value>,
+    pub content_html: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
@@ -0,0 +1,36 @@
+use axum::{
+    Json,
+    Extension, http::StatusCode,
+    extract::Path,
+};
+use sqlx::{PgPool, query};
+use uuid::Uuid;
+
+use crate::auth::CurrentUser;
+use crate::routes::sessions::RevokeResponse;
+
+pub async fn delete_handler(
+    Path(session_id): Path<String>,
+    Extension(pool): Extension<PgPool>,
+    Extension(auth_user): Extension<CurrentUser>
+) -> (StatusCode, Json<RevokeResponse>) {
+    let Ok(session_id) = Uuid::parse_str(&session_id) else {
+        return (StatusCode::NOT_FOUND, Json(RevokeResponse { revoked: 0, error: Some("Session not found".to_string()) }));
+    };
+
+    let session = query!(
+        r#"
+        UPDATE sessions SET revoked_at = NOW()
+        WHERE id = $1 AND user_id = $2 AND revoked_at IS NULL
+        RETURNING id
+        "#,
+        session_id,
+        Uuid::parse_str(&auth_user.id).unwrap()
+    )
+    .fetch_one(&pool);
+
+    match session.await {
+        Ok(_) => (StatusCode::OK, Json(RevokeResponse { revoked: 1, error: None })),
+        Err(_) => (StatusCode::NOT_FOUND, Json(RevokeResponse { revoked: 0, error: Some("Session not found".to_string()) })),
+    }
+}
@@ -0,0 +1,80 @@
+use axum::{
+    Json,
+    Extension, http::StatusCode,
+    extract::Query,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, query};
+use uuid::Uuid;
+
+use crate::auth::CurrentUser;
+use crate::session;
+
+pub async fn get_handler(Extension(pool): Extension<PgPool>, Extension(auth_user): Extension<CurrentUser>) -> (StatusCode, Json<SessionsResponse>) {
+    let sessions = query!(
+        r#"
+        SELECT id, user_agent, ip_address, created_at, last_seen_at FROM sessions
+        WHERE user_id = $1 AND revoked_at IS NULL AND expires_at > NOW()
+        ORDER BY last_seen_at DESC
+        "#,
+        Uuid::parse_str(&auth_user.id).unwrap()
+    )
+        .fetch_all(&pool)
+        .await
+        .expect("Failed to fetch sessions");
+
+    let sessions: Vec<Session> = sessions.into_iter().map(|session| Session {
+        current: auth_user.session_id.as_deref() == Some(session.id.to_string().as_str()),
+        id: session.id.to_string(),
+        user_agent: session.user_agent,
+        ip_address: session.ip_address,
+        created_at: session.created_at.to_string(),
+        last_seen_at: session.last_seen_at.to_string(),
+    }).collect();
+
+    (StatusCode::OK, Json(SessionsResponse { sessions, error: None }))
+}
+
+pub async fn delete_handler(
+    Extension(pool): Extension<PgPool>,
+    Extension(auth_user): Extension<CurrentUser>,
+    Query(params): Query<DeleteParams>
+) -> (StatusCode, Json<RevokeResponse>) {
+    let except = if params.keep_current.unwrap_or_default() {
+        auth_user.session_id.as_deref().and_then(|id| Uuid::parse_str(id).ok())
+    } else {
+        None
+    };
+
+    match session::revoke_all(&pool, Uuid::parse_str(&auth_user.id).unwrap(), except).await {
+        Ok(revoked) => (StatusCode::OK, Json(RevokeResponse { revoked, error: None })),
+        Err(status) => (status, Json(RevokeResponse { revoked: 0, error: Some("Failed to revoke sessions".to_string()) })),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct DeleteParams {
+    pub keep_current: Option<bool>,
+}
+
+#[derive(Serialize)]
+pub struct SessionsResponse {
+    sessions: Vec<Session>,
+    error: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct RevokeResponse {
+    pub revoked: u64,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct Session {
+    pub id: String,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    pub created_at: String,
+    pub last_seen_at: String,
+    pub current: bool,
+}
@@ -0,0 +1,97 @@
+use axum::{
+    Json,
+    Extension, http::StatusCode,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, query};
+use uuid::Uuid;
+
+use crate::auth::CurrentUser;
+use crate::style::MAX_PROFILE_CHARS;
+
+pub async fn get_handler(Extension(pool): Extension<PgPool>, Extension(auth_user): Extension<CurrentUser>) -> (StatusCode, Json<StyleResponse>) {
+    let style = query!(
+        r#"
+        SELECT profile, enabled, edited, docs_analyzed, generated_at, updated_at FROM writing_styles WHERE user_id = $1
+        "#,
+        Uuid::parse_str(&auth_user.id).unwrap()
+    )
+    .fetch_optional(&pool)
+    .await
+    .expect("Failed to fetch writing style");
+
+    let style = style.map(|style| Style {
+        profile: style.profile,
+        enabled: style.enabled,
+        edited: style.edited,
+        docs_analyzed: style.docs_analyzed,
+        generated_at: style.generated_at.map(|t| t.to_string()),
+        updated_at: style.updated_at.to_string(),
+    });
+    (StatusCode::OK, Json(StyleResponse { style, error: None }))
+}
+
+/// Replaces the profile with the user's own wording, or turns it on or off
+/// with `enabled`; either can be left out. An empty profile stops it being
+/// added to prompts.
+pub async fn put_handler(Extension(pool): Extension<PgPool>, Extension(auth_user): Extension<CurrentUser>, Json(payload): Json<StyleRequest>) -> (StatusCode, Json<StyleResponse>) {
+    let profile = payload.profile.as_deref().map(str::trim);
+    if profile.is_some_and(|profile| profile.chars().count() > MAX_PROFILE_CHARS) {
+        return (StatusCode::BAD_REQUEST, Json(StyleResponse { style: None, error: Some(format!("Profile must be at most {} characters", MAX_PROFILE_CHARS)) }));
+    }
+
+    let style = query!(
+        r#"
+        INSERT INTO writing_styles (user_id, profile, enabled, edited)
+        VALUES ($1, COALESCE($2, ''), COALESCE($3, TRUE), $2 IS NOT NULL)
+        ON CONFLICT (user_id) DO UPDATE SET
+            profile = COALESCE($2, writing_styles.profile),
+            enabled = COALESCE($3, writing_styles.enabled),
+            edited = writing_styles.edited OR $2 IS NOT NULL,
+            updated_at = NOW()
+        RETURNING profile, enabled, edited, docs_analyzed, generated_at, updated_at
+        "#,
+        Uuid::parse_str(&auth_user.id).unwrap(),
+        profile,
+        payload.enabled
+    )
+    .fetch_one(&pool)
+    .await
+    .expect("Failed to save writing style");
+
+    (StatusCode::OK, Json(StyleResponse {
+        style: Some(Style {
+            profile: style.profile,
+            enabled: style.enabled,
+            edited: style.edited,
+            docs_analyzed: style.docs_analyzed,
+            generated_at: style.generated_at.map(|t| t.to_string()),
+            updated_at: style.updated_at.to_string(),
+        }),
+        error: None,
+    }))
+}
+
+#[derive(Deserialize)]
+pub struct StyleRequest {
+    profile: Option<String>,
+    enabled: Option<bool>,
+}
+
+#[derive(Serialize)]
+pub struct StyleResponse {
+    pub style: Option<Style>,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct Style {
+    pub profile: String,
+    /// Whether the profile is added to AI prompts.
+    pub enabled: bool,
+    /// Whether the user has changed the profile since it was last generated.
+    pub edited: bool,
+    pub docs_analyzed: i32,
+    pub generated_at: Option<String>,
+    pub updated_at: String,
+}
@@ -0,0 +1,70 @@
+use axum::{
+    Json,
+    Extension, http::StatusCode,
+};
+use sqlx::{PgPool, query};
+use uuid::Uuid;
+
+use crate::ai::Provider;
+use crate::auth::CurrentUser;
+use crate::routes::style::{Style, StyleResponse};
+use crate::style::{self, MAX_PROFILE_CHARS};
+use crate::usage;
+
+/// Derives a fresh profile from the user's recent documents, replacing any
+/// edits. Counts against the user's AI quotas like any other request.
+pub async fn post_handler(Extension(pool): Extension<PgPool>, Extension(provider): Extension<Provider>, Extension(auth_user): Extension<CurrentUser>) -> (StatusCode, Json<StyleResponse>) {
+    let user_id = Uuid::parse_str(&auth_user.id).unwrap();
+
+    let (sample, docs_analyzed) = style::sample(&pool, user_id).await.expect("Failed to fetch docs");
+    if docs_analyzed == 0 {
+        return (StatusCode::BAD_REQUEST, Json(StyleResponse { style: None, error: Some("Write a document first so there is something to learn from".to_string()) }));
+    }
+
+    let usage_id = match usage::reserve(&pool, user_id, provider.model()).await.expect("Failed to check AI usage") {
+        Ok(usage_id) => usage_id,
+        Err(error) => return (StatusCode::TOO_MANY_REQUESTS, Json(StyleResponse { style: None, error: Some(error) })),
+    };
+    let completion = match style::describe(&provider, sample).await {
+        Ok(completion) => completion,
+        Err(err) => return (err.status(), Json(StyleResponse { style: None, error: Some(err.to_string()) })),
+    };
+    if let Some(tokens) = completion.usage {
+        usage::record(&pool, usage_id, tokens.prompt_tokens, tokens.completion_tokens)
+            .await
+            .expect("Failed to record AI usage");
+    }
+
+    let profile: String = completion.content.trim().chars().take(MAX_PROFILE_CHARS).collect();
+    let style = query!(
+        r#"
+        INSERT INTO writing_styles (user_id, profile, docs_analyzed, generated_at)
+        VALUES ($1, $2, $3, NOW())
+        ON CONFLICT (user_id) DO UPDATE SET
+            profile = EXCLUDED.profile,
+            edited = FALSE,
+            docs_analyzed = EXCLUDED.docs_analyzed,
+            generated_at = NOW(),
+            updated_at = NOW()
+        RETURNING profile, enabled, edited, docs_analyzed, generated_at, updated_at
+        "#,
+        user_id,
+        profile,
+        docs_analyzed
+    )
+    .fetch_one(&pool)
+    .await
+    .expect("Failed to save writing style");
+
+    (StatusCode::OK, Json(StyleResponse {
+        style: Some(Style {
+            profile: style.profile,
+            enabled: style.enabled,
+            edited: style.edited,
+            docs_analyzed: style.docs_analyzed,
+            generated_at: style.generated_at.map(|t| t.to_string()),
+            updated_at: style.updated_at.to_string(),
+        }),
+        error: None,
+    }))
+}
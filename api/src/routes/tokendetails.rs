@@ -0,0 +1,48 @@
+use axum::{
+    Json,
+    Extension, http::StatusCode,
+    extract::Path,
+};
+use sqlx::{PgPool, query};
+use uuid::Uuid;
+
+use crate::auth::{CurrentUser, Scope};
+use crate::routes::tokens::{Token, TokenResponse};
+
+pub async fn delete_handler(
+    Path(token_id): Path<String>,
+    Extension(pool): Extension<PgPool>,
+    Extension(auth_user): Extension<CurrentUser>
+) -> (StatusCode, Json<TokenResponse>) {
+    let Ok(token_id) = Uuid::parse_str(&token_id) else {
+        return (StatusCode::NOT_FOUND, Json(TokenResponse { token: None, error: Some("Token not found".to_string()) }));
+    };
+
+    let token = query!(
+        r#"
+        UPDATE personal_access_tokens SET revoked_at = NOW()
+        WHERE id = $1 AND user_id = $2 AND revoked_at IS NULL
+        RETURNING id, name, token_prefix, scope, created_at, last_used_at, expires_at
+        "#,
+        token_id,
+        Uuid::parse_str(&auth_user.id).unwrap()
+    )
+    .fetch_one(&pool);
+
+    match token.await {
+        Ok(token) => {
+            let token = Token {
+                id: token.id.to_string(),
+                name: token.name,
+                token_prefix: token.token_prefix,
+                scope: Scope::from_db(&token.scope),
+                created_at: token.created_at.to_string(),
+                last_used_at: token.last_used_at.map(|t| t.to_string()),
+                expires_at: token.expires_at.map(|t| t.to_string()),
+                token: None,
+            };
+            (StatusCode::OK, Json(TokenResponse { token: Some(token), error: None }))
+        }
+        Err(_) => (StatusCode::NOT_FOUND, Json(TokenResponse { token: None, error: Some("Token not found".to_string()) })),
+    }
+}
@@ -0,0 +1,35 @@
+use axum::{
+    Json,
+    Extension, http::StatusCode,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+
+use crate::session::{self, ClientInfo};
+
+pub async fn handler(Extension(pool): Extension<PgPool>, client: ClientInfo, Json(payload): Json<TokenRefreshRequest>) -> (StatusCode, Json<TokenRefreshResponse>) {
+    match session::refresh(&pool, &payload.refresh_token, &client).await {
+        Ok(tokens) => (StatusCode::OK, Json(TokenRefreshResponse {
+            access_token: Some(tokens.access_token),
+            refresh_token: Some(tokens.refresh_token),
+            error: None,
+        })),
+        Err(err) => (err.status_code, Json(TokenRefreshResponse {
+            access_token: None,
+            refresh_token: None,
+            error: Some(err.message),
+        })),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct TokenRefreshRequest {
+    refresh_token: String,
+}
+
+#[derive(Serialize)]
+pub struct TokenRefreshResponse {
+    access_token: Option<String>,
+    refresh_token: Option<String>,
+    error: Option<String>,
+}
@@ -0,0 +1,120 @@
+use axum::{
+    Json,
+    Extension, http::StatusCode,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, query};
+use uuid::Uuid;
+
+use crate::auth::{CurrentUser, Scope, PAT_PREFIX};
+use crate::session::{generate_token, hash_token};
+
+pub async fn get_handler(Extension(pool): Extension<PgPool>, Extension(auth_user): Extension<CurrentUser>) -> (StatusCode, Json<TokensResponse>) {
+    let tokens = query!(
+        r#"
+        SELECT id, name, token_prefix, scope, created_at, last_used_at, expires_at FROM personal_access_tokens
+        WHERE user_id = $1 AND revoked_at IS NULL
+        ORDER BY created_at DESC
+        "#,
+        Uuid::parse_str(&auth_user.id).unwrap()
+    )
+        .fetch_all(&pool)
+        .await
+        .expect("Failed to fetch tokens");
+
+    let tokens: Vec<Token> = tokens.into_iter().map(|token| Token {
+        id: token.id.to_string(),
+        name: token.name,
+        token_prefix: token.token_prefix,
+        scope: Scope::from_db(&token.scope),
+        created_at: token.created_at.to_string(),
+        last_used_at: token.last_used_at.map(|t| t.to_string()),
+        expires_at: token.expires_at.map(|t| t.to_string()),
+        token: None,
+    }).collect();
+
+    (StatusCode::OK, Json(TokensResponse { tokens, error: None }))
+}
+
+pub async fn post_handler(
+    Extension(pool): Extension<PgPool>,
+    Extension(auth_user): Extension<CurrentUser>,
+    Json(payload): Json<TokenRequest>
+) -> (StatusCode, Json<TokenResponse>) {
+    if payload.name.trim().is_empty() || payload.name.len() > 100 {
+        return (StatusCode::BAD_REQUEST, Json(TokenResponse { token: None, error: Some("Name must be between 1 and 100 characters".to_string()) }));
+    }
+    if payload.scope == Scope::Full {
+        return (StatusCode::BAD_REQUEST, Json(TokenResponse { token: None, error: Some("Scope must be either read or write".to_string()) }));
+    }
+    if payload.expires_in_days.is_some_and(|days| days <= 0) {
+        return (StatusCode::BAD_REQUEST, Json(TokenResponse { token: None, error: Some("Expiry must be at least one day".to_string()) }));
+    }
+
+    let (secret, _) = generate_token();
+    let plain = format!("{}{}", PAT_PREFIX, secret);
+    let token_prefix: String = plain.chars().take(PAT_PREFIX.len() + 6).collect();
+
+    let token = query!(
+        r#"
+        INSERT INTO personal_access_tokens (user_id, name, token_hash, token_prefix, scope, expires_at)
+        VALUES ($1, $2, $3, $4, $5, NOW() + make_interval(days => $6))
+        RETURNING id, name, token_prefix, scope, created_at, last_used_at, expires_at
+        "#,
+        Uuid::parse_str(&auth_user.id).unwrap(),
+        payload.name.trim(),
+        hash_token(&plain),
+        token_prefix,
+        payload.scope.as_db(),
+        payload.expires_in_days
+    )
+    .fetch_one(&pool)
+    .await
+    .expect("Failed to create token");
+
+    let result = Token {
+        id: token.id.to_string(),
+        name: token.name,
+        token_prefix: token.token_prefix,
+        scope: Scope::from_db(&token.scope),
+        created_at: token.created_at.to_string(),
+        last_used_at: token.last_used_at.map(|t| t.to_string()),
+        expires_at: token.expires_at.map(|t| t.to_string()),
+        token: Some(plain),
+    };
+
+    (StatusCode::OK, Json(TokenResponse { token: Some(result), error: None }))
+}
+
+#[derive(Deserialize)]
+pub struct TokenRequest {
+    name: String,
+    scope: Scope,
+    expires_in_days: Option<i32>,
+}
+
+#[derive(Serialize)]
+pub struct TokensResponse {
+    tokens: Vec<Token>,
+    error: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct TokenResponse {
+    pub token: Option<Token>,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct Token {
+    pub id: String,
+    pub name: String,
+    pub token_prefix: String,
+    pub scope: Scope,
+    pub created_at: String,
+    pub last_used_at: Option<String>,
+    pub expires_at: Option<String>,
+    /// The plain token, only returned once when it is created.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+}
@@ -0,0 +1,147 @@
+use axum::{
+    Json,
+    Extension, http::StatusCode,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, query};
+use uuid::Uuid;
+
+use crate::auth::CurrentUser;
+use crate::totp::{self, Verification};
+
+pub async fn get_handler(Extension(pool): Extension<PgPool>, Extension(auth_user): Extension<CurrentUser>) -> (StatusCode, Json<TotpResponse>) {
+    let status = query!(
+        r#"
+        SELECT totp_factors.enabled_at,
+            (SELECT COUNT(*) FROM totp_recovery_codes
+             WHERE totp_recovery_codes.user_id = totp_factors.user_id AND used_at IS NULL) AS "recovery_codes_remaining!"
+        FROM totp_factors
+        WHERE user_id = $1 AND enabled_at IS NOT NULL
+        "#,
+        Uuid::parse_str(&auth_user.id).unwrap()
+    )
+    .fetch_optional(&pool)
+    .await
+    .expect("Failed to fetch authenticator");
+
+    (StatusCode::OK, Json(TotpResponse {
+        enabled: status.is_some(),
+        enabled_at: status.as_ref().and_then(|status| status.enabled_at.map(|t| t.to_string())),
+        recovery_codes_remaining: status.map(|status| status.recovery_codes_remaining),
+        error: None,
+    }))
+}
+
+/// Starts enrollment with a fresh secret. The factor stays disabled until
+/// `/me/totp/confirm` receives a matching code.
+pub async fn post_handler(Extension(pool): Extension<PgPool>, Extension(auth_user): Extension<CurrentUser>) -> (StatusCode, Json<TotpEnrollResponse>) {
+    let secret = totp::generate_secret();
+    let Some(otpauth_url) = totp::otpauth_url(&secret, &auth_user.email) else {
+        return (StatusCode::INTERNAL_SERVER_ERROR, Json(TotpEnrollResponse {
+            secret: None,
+            otpauth_url: None,
+            error: Some("Failed to create authenticator secret".to_string()),
+        }));
+    };
+
+    let started = query!(
+        r#"
+        INSERT INTO totp_factors (user_id, secret) VALUES ($1, $2)
+        ON CONFLICT (user_id) DO UPDATE SET secret = EXCLUDED.secret, created_at = NOW()
+        WHERE totp_factors.enabled_at IS NULL
+        "#,
+        Uuid::parse_str(&auth_user.id).unwrap(),
+        secret
+    )
+    .execute(&pool)
+    .await
+    .expect("Failed to start authenticator enrollment");
+
+    if started.rows_affected() == 0 {
+        return (StatusCode::CONFLICT, Json(TotpEnrollResponse {
+            secret: None,
+            otpauth_url: None,
+            error: Some("Authenticator is already enabled".to_string()),
+        }));
+    }
+
+    (StatusCode::OK, Json(TotpEnrollResponse {
+        secret: Some(secret),
+        otpauth_url: Some(otpauth_url),
+        error: None,
+    }))
+}
+
+/// Turns the second factor off. Requires a current code so a stolen session
+/// alone can't downgrade the account.
+pub async fn delete_handler(Extension(pool): Extension<PgPool>, Extension(auth_user): Extension<CurrentUser>, Json(payload): Json<TotpCodeRequest>) -> (StatusCode, Json<TotpResponse>) {
+    let user_id = Uuid::parse_str(&auth_user.id).unwrap();
+    let verification = totp::verify(&pool, user_id, &payload.code)
+        .await
+        .expect("Failed to verify authenticator code");
+    let (status, error) = match verification {
+        Verification::Valid => (StatusCode::OK, None),
+        Verification::Invalid => (StatusCode::BAD_REQUEST, Some("Invalid authenticator code")),
+        Verification::Locked => (StatusCode::TOO_MANY_REQUESTS, Some("Too many attempts, please try again later")),
+    };
+    if let Some(error) = error {
+        return (status, Json(TotpResponse {
+            enabled: true,
+            enabled_at: None,
+            recovery_codes_remaining: None,
+            error: Some(error.to_string()),
+        }));
+    }
+
+    query!(
+        r#"
+        DELETE FROM totp_factors WHERE user_id = $1
+        "#,
+        user_id
+    )
+    .execute(&pool)
+    .await
+    .expect("Failed to delete authenticator");
+    query!(
+        r#"
+        DELETE FROM totp_recovery_codes WHERE user_id = $1
+        "#,
+        user_id
+    )
+    .execute(&pool)
+    .await
+    .expect("Failed to delete recovery codes");
+
+    (StatusCode::OK, Json(TotpResponse {
+        enabled: false,
+        enabled_at: None,
+        recovery_codes_remaining: None,
+        error: None,
+    }))
+}
+
+#[derive(Deserialize)]
+pub struct TotpCodeRequest {
+    pub code: String,
+}
+
+#[derive(Serialize)]
+pub struct TotpResponse {
+    enabled: bool,
+    enabled_at: Option<String>,
+    recovery_codes_remaining: Option<i64>,
+    error: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct TotpEnrollResponse {
+    secret: Option<String>,
+    otpauth_url: Option<String>,
+    error: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct RecoveryCodesResponse {
+    pub recovery_codes: Option<Vec<String>>,
+    pub error: Option<String>,
+}
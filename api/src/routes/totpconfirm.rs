@@ -0,0 +1,30 @@
+use axum::{
+    Json,
+    Extension, http::StatusCode,
+};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::auth::CurrentUser;
+use crate::routes::totp::{RecoveryCodesResponse, TotpCodeRequest};
+use crate::totp;
+
+/// Enables the authenticator and hands out the first set of recovery codes.
+pub async fn post_handler(Extension(pool): Extension<PgPool>, Extension(auth_user): Extension<CurrentUser>, Json(payload): Json<TotpCodeRequest>) -> (StatusCode, Json<RecoveryCodesResponse>) {
+    let user_id = Uuid::parse_str(&auth_user.id).unwrap();
+    let confirmed = totp::confirm(&pool, user_id, &payload.code)
+        .await
+        .expect("Failed to confirm authenticator");
+    if !confirmed {
+        return (StatusCode::BAD_REQUEST, Json(RecoveryCodesResponse {
+            recovery_codes: None,
+            error: Some("Invalid authenticator code".to_string()),
+        }));
+    }
+
+    let recovery_codes = totp::generate_recovery_codes(&pool, user_id)
+        .await
+        .expect("Failed to create recovery codes");
+
+    (StatusCode::OK, Json(RecoveryCodesResponse { recovery_codes: Some(recovery_codes), error: None }))
+}
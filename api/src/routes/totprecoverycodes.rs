@@ -0,0 +1,32 @@
+use axum::{
+    Json,
+    Extension, http::StatusCode,
+};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::auth::CurrentUser;
+use crate::routes::totp::{RecoveryCodesResponse, TotpCodeRequest};
+use crate::totp::{self, Verification};
+
+/// Replaces every recovery code, e.g. after the old ones were used up or exposed.
+pub async fn post_handler(Extension(pool): Extension<PgPool>, Extension(auth_user): Extension<CurrentUser>, Json(payload): Json<TotpCodeRequest>) -> (StatusCode, Json<RecoveryCodesResponse>) {
+    let user_id = Uuid::parse_str(&auth_user.id).unwrap();
+    let verification = totp::verify(&pool, user_id, &payload.code)
+        .await
+        .expect("Failed to verify authenticator code");
+    let (status, error) = match verification {
+        Verification::Valid => (StatusCode::OK, None),
+        Verification::Invalid => (StatusCode::BAD_REQUEST, Some("Invalid authenticator code")),
+        Verification::Locked => (StatusCode::TOO_MANY_REQUESTS, Some("Too many attempts, please try again later")),
+    };
+    if let Some(error) = error {
+        return (status, Json(RecoveryCodesResponse { recovery_codes: None, error: Some(error.to_string()) }));
+    }
+
+    let recovery_codes = totp::generate_recovery_codes(&pool, user_id)
+        .await
+        .expect("Failed to create recovery codes");
+
+    (StatusCode::OK, Json(RecoveryCodesResponse { recovery_codes: Some(recovery_codes), error: None }))
+}
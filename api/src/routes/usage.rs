@@ -0,0 +1,31 @@
+use axum::{
+    Json,
+    Extension, http::StatusCode,
+};
+use serde::Serialize;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::auth::CurrentUser;
+use crate::usage::{self, PeriodUsage};
+
+/// The signed-in user's AI usage so far today and this month, with the
+/// configured limits. A `null` limit means unlimited.
+pub async fn get_handler(Extension(pool): Extension<PgPool>, Extension(auth_user): Extension<CurrentUser>) -> (StatusCode, Json<UsageResponse>) {
+    let (daily, monthly) = usage::current(&pool, Uuid::parse_str(&auth_user.id).unwrap())
+        .await
+        .expect("Failed to fetch AI usage");
+
+    (StatusCode::OK, Json(UsageResponse {
+        daily: Some(daily),
+        monthly: Some(monthly),
+        error: None,
+    }))
+}
+
+#[derive(Serialize)]
+pub struct UsageResponse {
+    daily: Option<PeriodUsage>,
+    monthly: Option<PeriodUsage>,
+    error: Option<String>,
+}
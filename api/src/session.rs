@@ -0,0 +1,206 @@
+use axum::{
+    extract::{ConnectInfo, FromRequestParts},
+    http::{header::USER_AGENT, request::Parts, StatusCode},
+};
+use rand::Rng;
+use sha2::{Digest, Sha256};
+use sqlx::{PgPool, query};
+use std::{convert::Infallible, env::var, net::SocketAddr};
+use uuid::Uuid;
+
+use crate::auth::{encode_jwt, AuthError};
+
+pub struct Tokens {
+    pub access_token: String,
+    pub refresh_token: String,
+}
+
+/// Device details recorded against a session so users can recognise it later.
+pub struct ClientInfo {
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for ClientInfo {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let user_agent = parts.headers
+            .get(USER_AGENT)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.chars().take(512).collect());
+
+        // Only trust X-Forwarded-For when running behind a known proxy.
+        let forwarded_for = if var("TRUST_PROXY").is_ok_and(|v| v == "true") {
+            parts.headers
+                .get("x-forwarded-for")
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.split(',').next())
+                .map(|value| value.trim().to_string())
+        } else {
+            None
+        };
+        let ip_address = forwarded_for.or_else(|| {
+            parts.extensions
+                .get::<ConnectInfo<SocketAddr>>()
+                .map(|ConnectInfo(addr)| addr.ip().to_string())
+        });
+
+        Ok(ClientInfo { user_agent, ip_address })
+    }
+}
+
+/// Minutes an access token stays valid, `ACCESS_TOKEN_TTL_MINUTES` (default 15).
+pub fn access_token_ttl_minutes() -> i64 {
+    var("ACCESS_TOKEN_TTL_MINUTES").ok().and_then(|v| v.parse().ok()).unwrap_or(15)
+}
+
+/// Days a refresh token stays valid since its last use, `REFRESH_TOKEN_TTL_DAYS` (default 30).
+pub fn refresh_token_ttl_days() -> i32 {
+    var("REFRESH_TOKEN_TTL_DAYS").ok().and_then(|v| v.parse().ok()).unwrap_or(30)
+}
+
+/// Generates a random opaque token, returned alongside its stored hash.
+pub fn generate_token() -> (String, String) {
+    let bytes: [u8; 32] = rand::rng().random();
+    let token = hex::encode(bytes);
+    let hash = hash_token(&token);
+    (token, hash)
+}
+
+/// Tokens are high-entropy, so a fast digest is enough to look them up by hash.
+pub fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+pub async fn start(pool: &PgPool, user_id: Uuid, email: String, client: &ClientInfo) -> Result<Tokens, StatusCode> {
+    let (refresh_token, refresh_token_hash) = generate_token();
+
+    let session = query!(
+        r#"
+        INSERT INTO sessions (user_id, refresh_token_hash, expires_at, user_agent, ip_address)
+        VALUES ($1, $2, NOW() + make_interval(days => $3), $4, $5)
+        RETURNING id
+        "#,
+        user_id,
+        refresh_token_hash,
+        refresh_token_ttl_days(),
+        client.user_agent,
+        client.ip_address
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let access_token = encode_jwt(email, session.id.to_string())?;
+    Ok(Tokens { access_token, refresh_token })
+}
+
+pub async fn refresh(pool: &PgPool, refresh_token: &str, client: &ClientInfo) -> Result<Tokens, AuthError> {
+    let presented_hash = hash_token(refresh_token);
+    let (next_refresh_token, next_refresh_token_hash) = generate_token();
+
+    let session = query!(
+        r#"
+        UPDATE sessions SET
+            previous_refresh_token_hash = refresh_token_hash,
+            refresh_token_hash = $2,
+            last_seen_at = NOW(),
+            expires_at = NOW() + make_interval(days => $3),
+            user_agent = COALESCE($4, sessions.user_agent),
+            ip_address = COALESCE($5, sessions.ip_address)
+        FROM users
+        WHERE users.id = sessions.user_id
+            AND sessions.refresh_token_hash = $1
+            AND sessions.revoked_at IS NULL
+            AND sessions.expires_at > NOW()
+        RETURNING sessions.id, users.email
+        "#,
+        presented_hash,
+        next_refresh_token_hash,
+        refresh_token_ttl_days(),
+        client.user_agent,
+        client.ip_address
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(|_| AuthError {
+        message: "Failed to refresh session".to_string(),
+        status_code: StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let Some(session) = session else {
+        // A rotated-out token being replayed means it leaked, so kill the session.
+        query!(
+            r#"
+            UPDATE sessions SET revoked_at = NOW()
+            WHERE previous_refresh_token_hash = $1 AND revoked_at IS NULL
+            "#,
+            presented_hash
+        )
+        .execute(pool)
+        .await
+        .map_err(|_| AuthError {
+            message: "Failed to refresh session".to_string(),
+            status_code: StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+        return Err(AuthError {
+            message: "Invalid refresh token".to_string(),
+            status_code: StatusCode::UNAUTHORIZED
+        });
+    };
+
+    let access_token = encode_jwt(session.email, session.id.to_string())
+        .map_err(|status_code| AuthError {
+            message: "Failed to issue token".to_string(),
+            status_code
+        })?;
+    Ok(Tokens { access_token, refresh_token: next_refresh_token })
+}
+
+/// Bumps `last_seen_at`, at most once a minute to keep writes off the hot path.
+pub async fn touch(pool: &PgPool, session_id: Uuid) -> Result<(), StatusCode> {
+    query!(
+        r#"
+        UPDATE sessions SET last_seen_at = NOW()
+        WHERE id = $1 AND last_seen_at < NOW() - INTERVAL '1 minute'
+        "#,
+        session_id
+    )
+    .execute(pool)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(())
+}
+
+pub async fn revoke(pool: &PgPool, session_id: Uuid) -> Result<(), StatusCode> {
+    query!(
+        r#"
+        UPDATE sessions SET revoked_at = NOW() WHERE id = $1 AND revoked_at IS NULL
+        "#,
+        session_id
+    )
+    .execute(pool)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(())
+}
+
+pub async fn revoke_all(pool: &PgPool, user_id: Uuid, except: Option<Uuid>) -> Result<u64, StatusCode> {
+    let result = query!(
+        r#"
+        UPDATE sessions SET revoked_at = NOW()
+        WHERE user_id = $1 AND revoked_at IS NULL AND ($2::uuid IS NULL OR id <> $2)
+        "#,
+        user_id,
+        except
+    )
+    .execute(pool)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(result.rows_affected())
+}
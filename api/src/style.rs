@@ -0,0 +1,81 @@
+use sqlx::{PgPool, query};
+use uuid::Uuid;
+
+use crate::ai::{AiError, Completion, Message, Provider};
+
+/// Most recent documents read when deriving a profile.
+const SAMPLE_DOCS: i64 = 20;
+/// Characters taken from each document, so one long document doesn't drown
+/// out the rest.
+const SAMPLE_CHARS_PER_DOC: usize = 2000;
+/// Characters of writing sent to the model in total.
+const SAMPLE_CHARS: usize = 12000;
+/// Longest profile a user may save.
+pub const MAX_PROFILE_CHARS: usize = 2000;
+
+/// Excerpts of the user's most recently edited documents, and how many
+/// documents they came from.
+pub async fn sample(pool: &PgPool, user_id: Uuid) -> Result<(String, i32), sqlx::Error> {
+    let docs = query!(
+        r#"
+        SELECT content_text FROM docs
+        WHERE user_id = $1 AND length(trim(content_text)) > 0
+        ORDER BY updated_at DESC
+        LIMIT $2
+        "#,
+        user_id,
+        SAMPLE_DOCS
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut excerpts = Vec::new();
+    let mut remaining = SAMPLE_CHARS;
+    for doc in docs {
+        if remaining == 0 {
+            break;
+        }
+        let excerpt: String = doc.content_text.trim().chars().take(SAMPLE_CHARS_PER_DOC.min(remaining)).collect();
+        remaining -= excerpt.chars().count();
+        excerpts.push(excerpt);
+    }
+    Ok((excerpts.join("\n\n---\n\n"), excerpts.len() as i32))
+}
+
+/// Asks the model to describe the writing style of `sample`.
+pub async fn describe(provider: &Provider, sample: String) -> Result<Completion, AiError> {
+    let mut chat = provider.chat(vec![
+        Message::system(concat!(
+            "You analyze writing style. Below are excerpts from documents written by one person, separated by ---.\n\n",
+            "Describe how they write so another writer could imitate them: tone, formality, sentence length, ",
+            "vocabulary, punctuation and formatting habits, and any recurring phrases. ",
+            "Don't summarize what the documents are about.\n\n",
+            "Answer with at most 8 short bullet points and nothing else."
+        ).to_string()),
+        Message::user(sample),
+    ]);
+    chat.temperature = Some(0.2);
+    provider.complete(chat).await
+}
+
+/// The profile to add to prompts, if the user has one and hasn't turned it off.
+pub async fn profile(pool: &PgPool, user_id: Uuid) -> Result<Option<String>, sqlx::Error> {
+    let style = query!(
+        r#"
+        SELECT profile FROM writing_styles WHERE user_id = $1 AND enabled AND length(trim(profile)) > 0
+        "#,
+        user_id
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(style.map(|style| style.profile))
+}
+
+/// Appends the user's style profile to a system prompt.
+pub fn apply(system: String, profile: Option<&str>) -> String {
+    match profile {
+        Some(profile) => format!("{}\n\nWrite in the user's own style, described here:\n{}", system, profile),
+        None => system,
+    }
+}
@@ -0,0 +1,136 @@
+use minijinja::{context, Environment, Error, ErrorKind, Value};
+use std::{env::var, fs, path::PathBuf, sync::Arc};
+
+use crate::mailer::Email;
+
+/// Templates compiled into the binary, as `(name, source)`.
+const BUILTIN: &[(&str, &str)] = &[
+    ("layout.html", include_str!("../templates/email/layout.html")),
+    ("en/otp.subject.txt", include_str!("../templates/email/en/otp.subject.txt")),
+    ("en/otp.txt", include_str!("../templates/email/en/otp.txt")),
+    ("en/otp.html", include_str!("../templates/email/en/otp.html")),
+    ("en/share_invite.subject.txt", include_str!("../templates/email/en/share_invite.subject.txt")),
+    ("en/share_invite.txt", include_str!("../templates/email/en/share_invite.txt")),
+    ("en/share_invite.html", include_str!("../templates/email/en/share_invite.html")),
+    ("en/digest.subject.txt", include_str!("../templates/email/en/digest.subject.txt")),
+    ("en/digest.txt", include_str!("../templates/email/en/digest.txt")),
+    ("en/digest.html", include_str!("../templates/email/en/digest.html")),
+    ("es/otp.subject.txt", include_str!("../templates/email/es/otp.subject.txt")),
+    ("es/otp.txt", include_str!("../templates/email/es/otp.txt")),
+    ("es/otp.html", include_str!("../templates/email/es/otp.html")),
+    ("es/share_invite.subject.txt", include_str!("../templates/email/es/share_invite.subject.txt")),
+    ("es/share_invite.txt", include_str!("../templates/email/es/share_invite.txt")),
+    ("es/share_invite.html", include_str!("../templates/email/es/share_invite.html")),
+    ("es/digest.subject.txt", include_str!("../templates/email/es/digest.subject.txt")),
+    ("es/digest.txt", include_str!("../templates/email/es/digest.txt")),
+    ("es/digest.html", include_str!("../templates/email/es/digest.html")),
+];
+
+/// Renders account emails from `<locale>/<name>.subject.txt`, `<name>.txt`
+/// and `<name>.html` templates.
+///
+/// Files in `EMAIL_TEMPLATES_DIR` take precedence over the built-in ones with
+/// the same path, so operators can rebrand `layout.html` or add a locale
+/// without rebuilding. Templates are read once and cached until restart.
+#[derive(Clone)]
+pub struct Templates {
+    env: Arc<Environment<'static>>,
+    default_locale: String,
+}
+
+pub fn templates() -> Templates {
+    let dir = var("EMAIL_TEMPLATES_DIR").ok().filter(|dir| !dir.is_empty()).map(PathBuf::from);
+
+    let mut env = Environment::new();
+    env.set_loader(move |name| {
+        if let Some(dir) = &dir {
+            let path = dir.join(name);
+            if path.is_file() {
+                return fs::read_to_string(&path)
+                    .map(Some)
+                    .map_err(|err| Error::new(ErrorKind::InvalidOperation, format!("Failed to read {}", path.display())).with_source(err));
+            }
+        }
+        Ok(BUILTIN.iter().find(|(builtin, _)| *builtin == name).map(|(_, source)| source.to_string()))
+    });
+
+    Templates {
+        env: Arc::new(env),
+        default_locale: var("EMAIL_DEFAULT_LOCALE").ok().filter(|locale| !locale.is_empty()).unwrap_or_else(|| "en".to_string()),
+    }
+}
+
+/// Name shown in emails, `APP_NAME` (default `Notes`).
+fn app_name() -> String {
+    var("APP_NAME").ok().filter(|name| !name.is_empty()).unwrap_or_else(|| "Notes".to_string())
+}
+
+/// Cleans up a locale such as `pt-BR` from the user or `Accept-Language`,
+/// returning `None` for anything that isn't a plausible language tag.
+pub fn normalize_locale(locale: &str) -> Option<String> {
+    let locale = locale.split([',', ';']).next()?.trim().replace('_', "-").to_lowercase();
+    let valid = (2..=35).contains(&locale.len())
+        && locale.chars().all(|c| c.is_ascii_alphanumeric() || c == '-');
+    valid.then_some(locale)
+}
+
+impl Templates {
+    /// Renders `name` in the closest available locale, trying `pt-br`, then
+    /// `pt`, then the default locale.
+    pub fn render(&self, name: &str, locale: Option<&str>, ctx: Value) -> Result<Email, Error> {
+        let locale = self.resolve_locale(name, locale);
+        let ctx = context! {
+            app_name => app_name(),
+            app_url => var("APP_URL").ok().filter(|url| !url.is_empty()).unwrap_or_else(|| "http://localhost:3000".to_string()),
+            locale => locale,
+            ..ctx
+        };
+
+        let subject = self.env.get_template(&format!("{}/{}.subject.txt", locale, name))?.render(&ctx)?;
+        let text = self.env.get_template(&format!("{}/{}.txt", locale, name))?.render(&ctx)?;
+        let html = self.env.get_template(&format!("{}/{}.html", locale, name))?.render(&ctx)?;
+        Ok(Email {
+            subject: subject.lines().next().unwrap_or_default().trim().to_string(),
+            text,
+            html,
+        })
+    }
+
+    fn resolve_locale(&self, name: &str, locale: Option<&str>) -> String {
+        let mut candidates = Vec::new();
+        if let Some(locale) = locale.and_then(normalize_locale) {
+            if let Some((language, _)) = locale.split_once('-') {
+                candidates.push(language.to_string());
+            }
+            candidates.insert(0, locale);
+        }
+
+        candidates
+            .into_iter()
+            .find(|locale| self.env.get_template(&format!("{}/{}.subject.txt", locale, name)).is_ok())
+            .unwrap_or_else(|| self.default_locale.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_every_builtin_template() {
+        let templates = templates();
+        let docs = vec![context! { title => "Plans <draft>", link => "http://localhost:3000/docs/1" }, context! { title => "Ideas", link => "http://localhost:3000/docs/2" }];
+        let cases = [
+            ("otp", context! { code => "123456", link => "http://localhost:3000/login", ttl_minutes => 10 }),
+            ("share_invite", context! { inviter => "Ana", doc_title => "Plans <draft>", link => "http://localhost:3000/docs/1", message => "Have a look" }),
+            ("digest", context! { docs => docs }),
+        ];
+        for locale in ["en", "es"] {
+            for (name, ctx) in &cases {
+                let email = templates.render(name, Some(locale), ctx.clone()).unwrap();
+                assert!(!email.subject.is_empty());
+                assert!(!email.html.contains("<draft>"), "{}/{} doesn't escape the title", locale, name);
+            }
+        }
+    }
+}
@@ -0,0 +1,212 @@
+use chrono::Utc;
+use rand::Rng;
+use sqlx::{PgPool, query};
+use std::env::var;
+use totp_rs::{Algorithm, Secret, TOTP};
+use uuid::Uuid;
+
+use crate::session::hash_token;
+
+/// Seconds each code is valid for, as every authenticator app assumes.
+const STEP: u64 = 30;
+/// Steps either side of now that are still accepted, to allow for clock drift.
+const SKEW: i64 = 1;
+const RECOVERY_CODE_COUNT: usize = 10;
+/// Minutes the second factor stays locked after too many wrong codes.
+const LOCKOUT_MINUTES: i32 = 15;
+
+/// Name authenticator apps show next to the account, `TOTP_ISSUER` (default `Notes`).
+fn issuer() -> String {
+    var("TOTP_ISSUER").ok().filter(|issuer| !issuer.is_empty()).unwrap_or_else(|| "Notes".to_string())
+}
+
+/// Wrong codes in a row before the second factor is locked for
+/// `LOCKOUT_MINUTES`, `TOTP_MAX_ATTEMPTS` (default 5).
+fn max_attempts() -> i32 {
+    var("TOTP_MAX_ATTEMPTS").ok().and_then(|v| v.parse().ok()).unwrap_or(5)
+}
+
+pub enum Verification {
+    Valid,
+    Invalid,
+    /// Too many wrong codes were tried recently; nothing is checked until
+    /// the lock runs out.
+    Locked,
+}
+
+/// Generates a new base32 secret for enrollment.
+pub fn generate_secret() -> String {
+    Secret::generate_secret().to_encoded().to_string()
+}
+
+/// The `otpauth://` URL authenticator apps scan to add the account.
+pub fn otpauth_url(secret: &str, email: &str) -> Option<String> {
+    build(secret, email).map(|totp| totp.get_url())
+}
+
+fn build(secret: &str, email: &str) -> Option<TOTP> {
+    let bytes = Secret::Encoded(secret.to_string()).to_bytes().ok()?;
+    TOTP::new(Algorithm::SHA1, 6, SKEW as u8, STEP, bytes, Some(issuer()), email.to_string()).ok()
+}
+
+/// Returns the time step `code` was generated for, if it is valid around now.
+fn matching_step(secret: &str, code: &str) -> Option<i64> {
+    let totp = build(secret, "")?;
+    let now = Utc::now().timestamp() / STEP as i64;
+    (now - SKEW..=now + SKEW).find(|step| {
+        let expected = totp.generate(*step as u64 * STEP);
+        expected.len() == code.len() && expected.bytes().zip(code.bytes()).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+    })
+}
+
+pub async fn is_enabled(pool: &PgPool, user_id: Uuid) -> Result<bool, sqlx::Error> {
+    let factor = query!(
+        r#"
+        SELECT 1 AS "enabled!" FROM totp_factors WHERE user_id = $1 AND enabled_at IS NOT NULL
+        "#,
+        user_id
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(factor.is_some())
+}
+
+/// Enables a pending enrollment once the user proves their app produces
+/// matching codes.
+pub async fn confirm(pool: &PgPool, user_id: Uuid, code: &str) -> Result<bool, sqlx::Error> {
+    let factor = query!(
+        r#"
+        SELECT secret FROM totp_factors WHERE user_id = $1 AND enabled_at IS NULL
+        "#,
+        user_id
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    let Some(step) = factor.and_then(|factor| matching_step(&factor.secret, code.trim())) else {
+        return Ok(false);
+    };
+
+    let enabled = query!(
+        r#"
+        UPDATE totp_factors SET enabled_at = NOW(), last_used_step = $2
+        WHERE user_id = $1 AND enabled_at IS NULL
+        "#,
+        user_id,
+        step
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(enabled.rows_affected() == 1)
+}
+
+/// Checks a code from the authenticator app, falling back to an unused
+/// recovery code. Each app code and recovery code is accepted only once.
+///
+/// After `TOTP_MAX_ATTEMPTS` wrong codes in a row the factor is locked for
+/// `LOCKOUT_MINUTES`, so a stolen session or sign-in code can't be used to
+/// guess the six digits.
+pub async fn verify(pool: &PgPool, user_id: Uuid, code: &str) -> Result<Verification, sqlx::Error> {
+    let code = code.trim();
+    // The attempt is counted before the code is checked, so parallel guesses
+    // can't get past the limit. A lock that has run out starts a new count.
+    let factor = query!(
+        r#"
+        UPDATE totp_factors SET
+            failed_attempts = CASE WHEN locked_until IS NULL THEN failed_attempts ELSE 0 END + 1,
+            locked_until = CASE
+                WHEN CASE WHEN locked_until IS NULL THEN failed_attempts ELSE 0 END + 1 >= $2 THEN NOW() + make_interval(mins => $3)
+            END
+        WHERE user_id = $1 AND enabled_at IS NOT NULL AND (locked_until IS NULL OR locked_until <= NOW())
+        RETURNING secret
+        "#,
+        user_id,
+        max_attempts(),
+        LOCKOUT_MINUTES
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    let Some(factor) = factor else {
+        return Ok(if is_enabled(pool, user_id).await? { Verification::Locked } else { Verification::Invalid });
+    };
+
+    if let Some(step) = matching_step(&factor.secret, code) {
+        // Only move forwards, so a code seen by an attacker can't be replayed
+        // while it is still inside the window.
+        let used = query!(
+            r#"
+            UPDATE totp_factors SET last_used_step = $2, failed_attempts = 0, locked_until = NULL
+            WHERE user_id = $1 AND (last_used_step IS NULL OR last_used_step < $2)
+            "#,
+            user_id,
+            step
+        )
+        .execute(pool)
+        .await?;
+        return Ok(if used.rows_affected() == 1 { Verification::Valid } else { Verification::Invalid });
+    }
+
+    let used = query!(
+        r#"
+        UPDATE totp_recovery_codes SET used_at = NOW()
+        WHERE user_id = $1 AND code_hash = $2 AND used_at IS NULL
+        "#,
+        user_id,
+        hash_token(&normalize_recovery_code(code))
+    )
+    .execute(pool)
+    .await?;
+    if used.rows_affected() == 0 {
+        return Ok(Verification::Invalid);
+    }
+
+    query!(
+        r#"
+        UPDATE totp_factors SET failed_attempts = 0, locked_until = NULL WHERE user_id = $1
+        "#,
+        user_id
+    )
+    .execute(pool)
+    .await?;
+    Ok(Verification::Valid)
+}
+
+/// Replaces the user's recovery codes, returning the new ones in plain text.
+/// They are only ever shown once.
+pub async fn generate_recovery_codes(pool: &PgPool, user_id: Uuid) -> Result<Vec<String>, sqlx::Error> {
+    let codes: Vec<String> = (0..RECOVERY_CODE_COUNT).map(|_| {
+        let bytes: [u8; 5] = rand::rng().random();
+        let code = hex::encode(bytes);
+        format!("{}-{}", &code[..5], &code[5..])
+    }).collect();
+    let hashes: Vec<String> = codes.iter().map(|code| hash_token(&normalize_recovery_code(code))).collect();
+
+    let mut tx = pool.begin().await?;
+    query!(
+        r#"
+        DELETE FROM totp_recovery_codes WHERE user_id = $1
+        "#,
+        user_id
+    )
+    .execute(&mut *tx)
+    .await?;
+    query!(
+        r#"
+        INSERT INTO totp_recovery_codes (user_id, code_hash) SELECT $1, UNNEST($2::text[])
+        "#,
+        user_id,
+        &hashes
+    )
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    Ok(codes)
+}
+
+fn normalize_recovery_code(code: &str) -> String {
+    code.chars().filter(|c| c.is_ascii_alphanumeric()).collect::<String>().to_lowercase()
+}
@@ -0,0 +1,140 @@
+use serde::Serialize;
+use sqlx::{PgExecutor, PgPool, query};
+use std::env::var;
+use uuid::Uuid;
+
+/// Requests and tokens a user may spend per period. Zero means unlimited.
+#[derive(Clone, Copy)]
+struct Quota {
+    requests: i64,
+    tokens: i64,
+}
+
+/// `AI_DAILY_REQUESTS` (default 100) and `AI_DAILY_TOKENS` (default 200000).
+fn daily_quota() -> Quota {
+    Quota {
+        requests: var("AI_DAILY_REQUESTS").ok().and_then(|v| v.parse().ok()).unwrap_or(100),
+        tokens: var("AI_DAILY_TOKENS").ok().and_then(|v| v.parse().ok()).unwrap_or(200_000),
+    }
+}
+
+/// `AI_MONTHLY_REQUESTS` (default 2000) and `AI_MONTHLY_TOKENS` (default 2000000).
+fn monthly_quota() -> Quota {
+    Quota {
+        requests: var("AI_MONTHLY_REQUESTS").ok().and_then(|v| v.parse().ok()).unwrap_or(2000),
+        tokens: var("AI_MONTHLY_TOKENS").ok().and_then(|v| v.parse().ok()).unwrap_or(2_000_000),
+    }
+}
+
+/// A user's AI usage in the current calendar day or month (UTC).
+#[derive(Serialize)]
+pub struct PeriodUsage {
+    pub requests: i64,
+    pub prompt_tokens: i64,
+    pub completion_tokens: i64,
+    pub total_tokens: i64,
+    pub request_limit: Option<i64>,
+    pub token_limit: Option<i64>,
+    pub resets_at: String,
+}
+
+impl PeriodUsage {
+    fn exhausted(&self) -> bool {
+        self.request_limit.is_some_and(|limit| self.requests >= limit)
+            || self.token_limit.is_some_and(|limit| self.total_tokens >= limit)
+    }
+}
+
+pub async fn current(executor: impl PgExecutor<'_>, user_id: Uuid) -> Result<(PeriodUsage, PeriodUsage), sqlx::Error> {
+    let usage = query!(
+        r#"
+        SELECT
+            COUNT(*) FILTER (WHERE created_at >= date_trunc('day', NOW() AT TIME ZONE 'UTC')) AS "daily_requests!",
+            COALESCE(SUM(prompt_tokens) FILTER (WHERE created_at >= date_trunc('day', NOW() AT TIME ZONE 'UTC')), 0) AS "daily_prompt_tokens!",
+            COALESCE(SUM(completion_tokens) FILTER (WHERE created_at >= date_trunc('day', NOW() AT TIME ZONE 'UTC')), 0) AS "daily_completion_tokens!",
+            COUNT(*) AS "monthly_requests!",
+            COALESCE(SUM(prompt_tokens), 0) AS "monthly_prompt_tokens!",
+            COALESCE(SUM(completion_tokens), 0) AS "monthly_completion_tokens!",
+            (date_trunc('day', NOW() AT TIME ZONE 'UTC') + INTERVAL '1 day')::timestamp AS "daily_resets_at!",
+            (date_trunc('month', NOW() AT TIME ZONE 'UTC') + INTERVAL '1 month')::timestamp AS "monthly_resets_at!"
+        FROM ai_usage
+        WHERE user_id = $1 AND created_at >= date_trunc('month', NOW() AT TIME ZONE 'UTC')
+        "#,
+        user_id
+    )
+    .fetch_one(executor)
+    .await?;
+
+    let period = |requests: i64, prompt_tokens: i64, completion_tokens: i64, quota: Quota, resets_at: String| PeriodUsage {
+        requests,
+        prompt_tokens,
+        completion_tokens,
+        total_tokens: prompt_tokens + completion_tokens,
+        request_limit: Some(quota.requests).filter(|limit| *limit > 0),
+        token_limit: Some(quota.tokens).filter(|limit| *limit > 0),
+        resets_at,
+    };
+    Ok((
+        period(usage.daily_requests, usage.daily_prompt_tokens, usage.daily_completion_tokens, daily_quota(), usage.daily_resets_at.to_string()),
+        period(usage.monthly_requests, usage.monthly_prompt_tokens, usage.monthly_completion_tokens, monthly_quota(), usage.monthly_resets_at.to_string()),
+    ))
+}
+
+/// Checks the user's quotas and records the request before it is sent. The
+/// check and the insert run in one transaction holding a lock on the user, so
+/// parallel requests can't all slip under the limit. Returns the usage row to
+/// fill in with token counts, or the reason the request is refused.
+pub async fn reserve(pool: &PgPool, user_id: Uuid, model: &str) -> Result<Result<Uuid, String>, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    query!(
+        r#"
+        SELECT id FROM users WHERE id = $1 FOR UPDATE
+        "#,
+        user_id
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    let (daily, monthly) = current(&mut *tx, user_id).await?;
+    if daily.exhausted() {
+        return Ok(Err(format!("Daily AI quota reached, it resets at {} UTC", daily.resets_at)));
+    }
+    if monthly.exhausted() {
+        return Ok(Err(format!("Monthly AI quota reached, it resets at {} UTC", monthly.resets_at)));
+    }
+
+    // Stored in UTC, like the windows `current` counts it in.
+    let usage = query!(
+        r#"
+        INSERT INTO ai_usage (user_id, model, created_at) VALUES ($1, $2, NOW() AT TIME ZONE 'UTC') RETURNING id
+        "#,
+        user_id,
+        model
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok(Ok(usage.id))
+}
+
+/// Rough token count of `chars` characters of text, for providers that don't
+/// report usage.
+pub fn estimate_tokens(chars: usize) -> i32 {
+    chars.div_ceil(4).try_into().unwrap_or(i32::MAX)
+}
+
+/// Records the token counts reported by the provider for a reserved request.
+pub async fn record(pool: &PgPool, usage_id: Uuid, prompt_tokens: i32, completion_tokens: i32) -> Result<(), sqlx::Error> {
+    query!(
+        r#"
+        UPDATE ai_usage SET prompt_tokens = $2, completion_tokens = $3 WHERE id = $1
+        "#,
+        usage_id,
+        prompt_tokens,
+        completion_tokens
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
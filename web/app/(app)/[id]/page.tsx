@@ -9,6 +9,7 @@ import { Separator } from '@/components/ui/separator'
 import { SidebarTrigger } from '@/components/ui/sidebar'
 import { Tooltip, TooltipContent, TooltipTrigger } from '@/components/ui/tooltip'
 import { useUser } from '@/hooks/use-user'
+import { apiFetch } from '@/lib/api'
 import { ReloadIcon } from '@radix-ui/react-icons'
 import { Editor, JSONContent } from '@tiptap/react'
 import { CheckIcon, Trash2Icon, TriangleAlertIcon } from 'lucide-react'
@@ -34,11 +35,10 @@ export default function Page() {
     const fetchDoc = useCallback(async () => {
       if (user === null) r.replace('/')
 
-      const res = await fetch(`${process.env.NEXT_PUBLIC_API_URL}/docs/${decodeURIComponent(params.id?.toString() || '').split(':')[0]}`, {
+      const res = await apiFetch(`/docs/${decodeURIComponent(params.id?.toString() || '').split(':')[0]}`, {
         method: 'GET',
         headers: {
           'Content-Type': 'application/json',
-          Authorization: `Bearer ${localStorage.getItem('access_token')}`,
         },
       })
       if (res.ok) {
@@ -81,7 +81,7 @@ export default function Page() {
                 setLoading(true)
                 const formData = new FormData(e.currentTarget)
 
-                const res = await fetch(`${process.env.NEXT_PUBLIC_API_URL}/docs/${doc?.id}`, {
+                const res = await apiFetch(`/docs/${doc?.id}`, {
                   method: 'PUT',
                   body: JSON.stringify({
                     title: formData.get('title'),
@@ -91,7 +91,6 @@ export default function Page() {
                   }),
                   headers: {
                     'Content-Type': 'application/json',
-                    Authorization: `Bearer ${localStorage.getItem('access_token')}`,
                   },
                 })
                 setLoading(false)
@@ -141,7 +140,7 @@ export default function Page() {
                 <Button size="sm" onClick={async () => {
                   if (!user || !editor) return
                   setLoading(true)
-                  const res = await fetch(`${process.env.NEXT_PUBLIC_API_URL}/docs/${doc?.id}`, {
+                  const res = await apiFetch(`/docs/${doc?.id}`, {
                     method: 'PUT',
                     body: JSON.stringify({
                       title: doc?.title || 'Untitled Document',
@@ -151,7 +150,6 @@ export default function Page() {
                     }),
                     headers: {
                       'Content-Type': 'application/json',
-                      Authorization: `Bearer ${localStorage.getItem('access_token')}`,
                     },
                   })
                   await new Promise(resolve => setTimeout(resolve, 200))
@@ -191,11 +189,10 @@ export default function Page() {
                 <div className="flex justify-end">
                   <Button variant="outline" size="sm" className="gap-2 !text-red-400" onClick={async () => {
                     setLoading(true)
-                    const res = await fetch(`${process.env.NEXT_PUBLIC_API_URL}/docs/${doc?.id}`, {
+                    const res = await apiFetch(`/docs/${doc?.id}`, {
                       method: 'DELETE',
                       headers: {
                         'Content-Type': 'application/json',
-                        Authorization: `Bearer ${localStorage.getItem('access_token')}`,
                       },
                     })
                     setLoading(false)
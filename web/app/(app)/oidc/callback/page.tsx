@@ -0,0 +1,125 @@
+'use client'
+
+import { Button } from '@/components/ui/button'
+import { Input } from '@/components/ui/input'
+import { useUser } from '@/hooks/use-user'
+import { ReloadIcon } from '@radix-ui/react-icons'
+import { useRouter, useSearchParams } from 'next/navigation'
+import { Suspense, useCallback, useEffect, useRef, useState } from 'react'
+import { toast } from 'sonner'
+
+function OidcCallback() {
+  const r = useRouter()
+  const searchParams = useSearchParams()
+  const { fetchUser } = useUser()
+  const submitted = useRef(false)
+  const [totpRequired, setTotpRequired] = useState(false)
+  const [totpCode, setTotpCode] = useState('')
+  const [loading, setLoading] = useState(false)
+
+  const signIn = useCallback(async (code: string, state: string, totpCode?: string) => {
+    setLoading(true)
+    const resp = await fetch(`${process.env.NEXT_PUBLIC_API_URL}/oidc/login/finish`, {
+      method: 'POST',
+      body: JSON.stringify({ code, state, totp_code: totpCode }),
+      headers: {
+        'Content-Type': 'application/json',
+      },
+    })
+    setLoading(false)
+    const json = await resp.json() as {
+      access_token: string | null
+      refresh_token: string | null
+      totp_required: boolean | null
+      error: string | null
+    }
+    if (json.totp_required) {
+      if (totpCode) {
+        toast('Error', {
+          description: json.error,
+        })
+      }
+      setTotpCode('')
+      setTotpRequired(true)
+      return
+    }
+    sessionStorage.removeItem('oidc_state')
+    if (!resp.ok || !json.access_token || !json.refresh_token) {
+      toast('Error', {
+        description: json.error || 'Unable to sign in.',
+      })
+      r.replace('/')
+      return
+    }
+
+    localStorage.setItem('access_token', json.access_token)
+    localStorage.setItem('refresh_token', json.refresh_token)
+    toast('Success', {
+      description: 'Logged in successfully.',
+    })
+    fetchUser()
+    r.replace('/')
+  }, [fetchUser, r])
+
+  useEffect(() => {
+    const code = searchParams.get('code')
+    const state = searchParams.get('state')
+    if (submitted.current) return
+    submitted.current = true
+
+    // Only finish sign-ins this browser started.
+    if (!code || !state || state !== sessionStorage.getItem('oidc_state')) {
+      toast('Error', {
+        description: searchParams.get('error_description') || 'Unable to sign in.',
+      })
+      r.replace('/')
+      return
+    }
+
+    signIn(code, state)
+  }, [searchParams, signIn, r])
+
+  if (totpRequired) {
+    return <div className="flex h-svh items-center justify-center">
+      <form className="w-full max-w-sm space-y-4 p-4" onSubmit={e => {
+        e.preventDefault()
+        const code = searchParams.get('code')
+        const state = searchParams.get('state')
+        if (!code || !state || !totpCode) return
+
+        signIn(code, state, totpCode)
+      }}>
+        <div className="space-y-1">
+          <h1 className="text-lg font-semibold">Two-factor Authentication</h1>
+          <p className="text-sm text-muted-foreground">
+            Enter the code from your authenticator app, or one of your recovery codes.
+          </p>
+        </div>
+        <Input
+          placeholder="123456"
+          autoComplete="one-time-code"
+          autoFocus
+          required
+          readOnly={loading}
+          value={totpCode}
+          onChange={e => setTotpCode(e.target.value)}
+        />
+        <Button size="sm" type="submit" className="w-full" disabled={loading}>
+          {loading ? <ReloadIcon className="animate-spin !size-4" /> : <></>}
+          Verify
+        </Button>
+      </form>
+    </div>
+  }
+
+  return <div className="flex h-svh items-center justify-center gap-2 text-muted-foreground">
+    <ReloadIcon className="animate-spin !size-4" />
+    Signing you in...
+  </div>
+}
+
+export default function Page() {
+  return <Suspense>
+    <OidcCallback />
+  </Suspense>
+}
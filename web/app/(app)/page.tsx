@@ -10,6 +10,7 @@ import {
   SidebarTrigger
 } from '@/components/ui/sidebar'
 import { useUser } from '@/hooks/use-user'
+import { apiFetch } from '@/lib/api'
 import { Editor } from '@tiptap/react'
 import { Content } from '@tiptap/react'
 import { Edit3Icon } from 'lucide-react'
@@ -64,7 +65,7 @@ export default function Page() {
               if (!editor) return
               setLoading(true)
               const formData = new FormData(e.currentTarget)
-              const res = await fetch(`${process.env.NEXT_PUBLIC_API_URL}/docs`, {
+              const res = await apiFetch(`/docs`, {
                 method: 'POST',
                 body: JSON.stringify({
                   title: formData.get('title'),
@@ -74,7 +75,6 @@ export default function Page() {
                 }),
                 headers: {
                   'Content-Type': 'application/json',
-                  Authorization: `Bearer ${localStorage.getItem('access_token')}`,
                 },
               })
               setLoading(false)
@@ -30,6 +30,7 @@ import {
 } from '@/components/ui/sidebar'
 import { Tabs, TabsList, TabsTrigger } from '@/components/ui/tabs'
 import { useUser } from '@/hooks/use-user'
+import { apiFetch } from '@/lib/api'
 import { cn } from '@/lib/utils'
 import { zodResolver } from '@hookform/resolvers/zod'
 import { GitHubLogoIcon } from '@radix-ui/react-icons'
@@ -46,7 +47,7 @@ import {
 import { useTheme } from 'next-themes'
 import Link from 'next/link'
 import { usePathname } from 'next/navigation'
-import { useCallback, useEffect, useState } from 'react'
+import { useCallback, useEffect, useRef, useState } from 'react'
 import { useForm } from 'react-hook-form'
 import { toast } from 'sonner'
 import { z } from 'zod'
@@ -91,18 +92,20 @@ export function AppSidebar({ ...props }: React.ComponentProps<typeof Sidebar>) {
     })
   }, [p, docs])
 
-  const fetchDocs = useCallback(async () => {
+  const [nextCursor, setNextCursor] = useState<string | null>(null)
+
+  const fetchDocs = useCallback(async (cursor?: string) => {
     if (user) {
-      const res = await fetch(`${process.env.NEXT_PUBLIC_API_URL}/docs`, {
+      const res = await apiFetch(`/docs${cursor ? `?cursor=${encodeURIComponent(cursor)}` : ''}`, {
         method: 'GET',
         headers: {
           'Content-Type': 'application/json',
-          Authorization: `Bearer ${localStorage.getItem('access_token')}`,
         },
       })
       if (res.ok) {
         const data = await res.json()
-        setDocs(data?.docs)
+        setDocs(docs => cursor ? [...docs, ...data.docs] : data?.docs)
+        setNextCursor(data?.next_cursor)
       }
     }
   }, [user])
@@ -115,8 +118,36 @@ export function AppSidebar({ ...props }: React.ComponentProps<typeof Sidebar>) {
   const [searchDocs, setSearchDocs] = useState<{
     id: string
     title: string
-    content_text: string
+    snippet: string
   }[]>()
+  const latestSearch = useRef('')
+
+  const runSearch = useCallback(async (search: string) => {
+    latestSearch.current = search
+    const resp = await apiFetch(`/docs?search=${encodeURIComponent(search)}`, {
+      headers: {
+        'Content-Type': 'application/json',
+      },
+    })
+    if (latestSearch.current !== search) return
+    if (!resp.ok) {
+      toast('Error', {
+        description: await resp.text(),
+      })
+      return
+    }
+    const json = await resp.json()
+    setSearchDocs(json.docs)
+  }, [])
+
+  const searchText = searchForm.watch('search')
+  useEffect(() => {
+    const search = searchText.trim()
+    if (!search) return
+    const timeout = setTimeout(() => runSearch(search), 250)
+    return () => clearTimeout(timeout)
+  }, [searchText, runSearch])
+
   const { setOpenMobile } = useSidebar()
 
   return (
@@ -162,23 +193,7 @@ export function AppSidebar({ ...props }: React.ComponentProps<typeof Sidebar>) {
               </DialogTrigger>
               <DialogContent className="[&_button.absolute.top-4.right-4]:hidden p-0 max-w-full">
                 <Form {...searchForm}>
-                  <form onSubmit={searchForm.handleSubmit(async (data) => {
-                    setSearchDocs(undefined)
-                    const resp = await fetch(`${process.env.NEXT_PUBLIC_API_URL}/docs?search=${encodeURIComponent(data.search.split(' ').join(' & '))}`, {
-                      headers: {
-                        'Content-Type': 'application/json',
-                        Authorization: `Bearer ${localStorage.getItem('access_token')}`,
-                      },
-                    })
-                    if (!resp.ok) {
-                      toast('Error', {
-                        description: await resp.text(),
-                      })
-                      return
-                    }
-                    const json = await resp.json()
-                    setSearchDocs(json.docs)
-                  })}>
+                  <form onSubmit={searchForm.handleSubmit((data) => runSearch(data.search))}>
                     <DialogHeader className="p-0">
                       <FormField
                         control={searchForm.control}
@@ -222,23 +237,8 @@ export function AppSidebar({ ...props }: React.ComponentProps<typeof Sidebar>) {
                             <h4 className="group-hover:underline underline-offset-4 scroll-m-20 text-base font-semibold tracking-tight">
                               {item.title}
                             </h4>
-                            <p className="text-muted-foreground line-clamp-2 text-sm" dangerouslySetInnerHTML={{
-                              __html: (() => {
-                                const words = searchForm.watch('search').split(' ')
-                                const regex = new RegExp(`(${words.join('|')})`, 'gi')
-                                if (!item.content_text.toLowerCase().includes(searchForm.watch('search').toLowerCase())) {
-                                  return item.content_text.replace(regex, (match) => `<strong>${match}</strong>`)
-                                }
-                                if (item.content_text.toLowerCase().indexOf(searchForm.watch('search').toLowerCase()) <= 65) {
-                                  return item.content_text.replace(regex, (match) => `<strong>${match}</strong>`)
-                                }
-                                if (item.content_text.toLowerCase().indexOf(searchForm.watch('search').toLowerCase()) > 65) {
-                                  return item.content_text.substring(
-                                    item.content_text.toLowerCase().indexOf(searchForm.watch('search').toLowerCase()) - 65,
-                                  ).replace(regex, (match) => `<strong>${match}</strong>`)
-                                }
-                                return item.content_text.replace(regex, (match) => `<strong>${match}</strong>`)
-                              })(),
+                            <p className="text-muted-foreground line-clamp-2 text-sm [&_mark]:bg-transparent [&_mark]:font-bold [&_mark]:text-foreground" dangerouslySetInnerHTML={{
+                              __html: item.snippet,
                             }}></p>
                           </Link>
                         ))}
@@ -303,11 +303,10 @@ export function AppSidebar({ ...props }: React.ComponentProps<typeof Sidebar>) {
                           <div className="flex justify-end">
                             <Button variant="outline" size="sm" className="gap-2 !text-red-400" onClick={async () => {
                               setLoading(true)
-                              const res = await fetch(`${process.env.NEXT_PUBLIC_API_URL}/docs/${item?.url.split('/').at(-1)}`, {
+                              const res = await apiFetch(`/docs/${item?.url.split('/').at(-1)}`, {
                                 method: 'DELETE',
                                 headers: {
                                   'Content-Type': 'application/json',
-                                  Authorization: `Bearer ${localStorage.getItem('access_token')}`,
                                 },
                               })
                               setLoading(false)
@@ -333,6 +332,11 @@ export function AppSidebar({ ...props }: React.ComponentProps<typeof Sidebar>) {
                 </DropdownMenu> */}
               </SidebarMenuItem>
             ))}
+            {nextCursor ? <SidebarMenuItem key="more">
+              <SidebarMenuButton className="truncate text-muted-foreground hover:cursor-pointer" onClick={() => fetchDocs(nextCursor)}>
+                <span className="truncate">Load more</span>
+              </SidebarMenuButton>
+            </SidebarMenuItem> : <></>}
           </SidebarMenu>
         </SidebarGroup>
       </SidebarContent>
@@ -356,8 +360,10 @@ export function AppSidebar({ ...props }: React.ComponentProps<typeof Sidebar>) {
               </CollapsibleTrigger>
               <CollapsibleContent>
                 <div className="p-0.5 pt-0 grid grid-cols-1 gap-2">
-                  <Button variant="ghost" className="w-full justify-start gap-2 rounded-xl" onClick={() => {
+                  <Button variant="ghost" className="w-full justify-start gap-2 rounded-xl" onClick={async () => {
+                    await apiFetch('/logout', { method: 'POST' }).catch(() => null)
                     localStorage.removeItem('access_token')
+                    localStorage.removeItem('refresh_token')
                     location.replace('/')
                   }}>
                     <LogOutIcon className="!size-4 !text-red-400" />
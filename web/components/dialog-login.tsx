@@ -19,7 +19,79 @@ export function DialogLogin({ title, description, children }: {
   const [loading, setLoading] = useState(false)
   const [email, setEmail] = useState('')
   const [otp, setOtp] = useState('')
-  const [step, setStep] = useState<'ask-email' | 'ask-otp'>('ask-email')
+  const [totpCode, setTotpCode] = useState('')
+  const [step, setStep] = useState<'ask-email' | 'ask-otp' | 'ask-totp'>('ask-email')
+
+  const verify = async () => {
+    setLoading(true)
+    const resp = await fetch(`${process.env.NEXT_PUBLIC_API_URL}/otp-verify`, {
+      method: 'POST',
+      body: JSON.stringify({
+        email: email,
+        verification_code: otp,
+        totp_code: step === 'ask-totp' ? totpCode : undefined,
+      }),
+      headers: {
+        'Content-Type': 'application/json',
+      },
+    })
+    setLoading(false)
+    const json = await resp.json() as {
+      access_token: string | null
+      refresh_token: string | null
+      totp_required: boolean | null
+      error: string | null
+    }
+    if (json.totp_required) {
+      if (step === 'ask-totp') {
+        toast('Error', {
+          description: json.error,
+        })
+      }
+      setTotpCode('')
+      setStep('ask-totp')
+      return
+    }
+    if (!resp.ok || !json.access_token || !json.refresh_token) {
+      toast('Error', {
+        description: json.error,
+      })
+      return
+    }
+    toast('Success', {
+      description: 'Logged in successfully.',
+    })
+
+    localStorage.setItem('access_token', json.access_token)
+    localStorage.setItem('refresh_token', json.refresh_token)
+    setTimeout(() => {
+      fetchUser()
+    }, 500)
+
+    setOpenLogin(false)
+  }
+
+  const startSso = async () => {
+    setLoading(true)
+    const resp = await fetch(`${process.env.NEXT_PUBLIC_API_URL}/oidc/login/start`, {
+      method: 'POST',
+    })
+    const json = await resp.json() as {
+      authorization_url: string | null
+      error: string | null
+    }
+    if (!resp.ok || !json.authorization_url) {
+      setLoading(false)
+      toast('Error', {
+        description: json.error,
+      })
+      return
+    }
+
+    // Checked by the callback page so only sign-ins started here complete.
+    sessionStorage.setItem('oidc_state', new URL(json.authorization_url).searchParams.get('state') || '')
+    window.location.href = json.authorization_url
+  }
 
   return <Dialog open={openLogin} onOpenChange={setOpenLogin}>
     <DialogTrigger asChild>
@@ -28,10 +100,10 @@ export function DialogLogin({ title, description, children }: {
     <DialogContent className="sm:max-w-sm">
       <DialogHeader>
         <DialogTitle>
-          {step === 'ask-otp' ? 'Input One-time Password' : title || 'Sign in with Email'}
+          {step === 'ask-totp' ? 'Two-factor Authentication' : step === 'ask-otp' ? 'Input One-time Password' : title || 'Sign in with Email'}
         </DialogTitle>
         <DialogDescription>
-          {step === 'ask-otp' ? 'Check your email to get the one-time password.' : description || 'Enter your email address to receive a one-time password for signing in.'}
+          {step === 'ask-totp' ? 'Enter the code from your authenticator app, or one of your recovery codes.' : step === 'ask-otp' ? 'Check your email to get the one-time password.' : description || 'Enter your email address to receive a one-time password for signing in.'}
         </DialogDescription>
       </DialogHeader>
       {step === 'ask-email' ? <form onSubmit={async e => {
@@ -48,16 +120,31 @@ export function DialogLogin({ title, description, children }: {
             'Content-Type': 'application/json',
           },
         })
-        await new Promise(resolve => setTimeout(resolve, 2500))
+        const json = await resp.json() as { email_id: string | null, error: string | null }
+        if (!resp.ok || !json.email_id) {
+          setLoading(false)
+          toast('Error', {
+            description: json.error,
+          })
+          return
+        }
+
+        // The email is sent in the background, so wait briefly for the outcome.
+        let status = 'pending'
+        for (let i = 0; i < 20 && status === 'pending'; i++) {
+          await new Promise(resolve => setTimeout(resolve, 500))
+          const statusResp = await fetch(`${process.env.NEXT_PUBLIC_API_URL}/emails/${json.email_id}`)
+          status = statusResp.ok ? (await statusResp.json() as { status: string }).status : 'pending'
+        }
         setLoading(false)
-        if (!resp.ok) {
+        if (status === 'failed') {
           toast('Error', {
-            description: await resp.text(),
+            description: 'We could not send the email. Please try again later.',
           })
           return
         }
         toast('Success', {
-          description: 'Check your email for the OTP.',
+          description: status === 'sent' ? 'Check your email for the OTP.' : 'Your OTP is on its way, it may take a few minutes to arrive.',
         })
         setStep('ask-otp')
       }}>
@@ -71,6 +158,16 @@ export function DialogLogin({ title, description, children }: {
             value={email}
             onChange={e => setEmail(e.target.value)}
           />
+          {process.env.NEXT_PUBLIC_OIDC_ENABLED === 'true' ? <Button
+            size="sm"
+            type="button"
+            variant="outline"
+            className="w-full"
+            disabled={loading}
+            onClick={startSso}
+          >
+            Continue with SSO
+          </Button> : <></>}
         </div>
         <DialogFooter>
           <DialogClose asChild>
@@ -88,37 +185,7 @@ export function DialogLogin({ title, description, children }: {
         e.preventDefault()
         if (!email || !otp) return
 
-        setLoading(true)
-        const resp = await fetch(`${process.env.NEXT_PUBLIC_API_URL}/otp-verify`, {
-          method: 'POST',
-          body: JSON.stringify({
-            email: email,
-            verification_code: otp,
-          }),
-          headers: {
-            'Content-Type': 'application/json',
-          },
-        })
-        setLoading(false)
-        if (!resp.ok) {
-          toast('Error', {
-            description: await resp.text(),
-          })
-          return
-        }
-        const json = await resp.json() as {
-          access_token: string
-        }
-        toast('Success', {
-          description: 'Logged in successfully.',
-        })
-
-        localStorage.setItem('access_token', json.access_token)
-        setTimeout(() => {
-          fetchUser()
-        }, 500)
-
-        setOpenLogin(false)
+        await verify()
       }}>
         <div className="space-y-2 pb-6">
           <InputOTP maxLength={6} value={otp} onChange={setOtp} autoFocus>
@@ -147,6 +214,35 @@ export function DialogLogin({ title, description, children }: {
           </Button>
         </DialogFooter>
       </form> : <></>}
+      {step === 'ask-totp' ? <form onSubmit={async e => {
+        e.preventDefault()
+        if (!email || !otp || !totpCode) return
+
+        await verify()
+      }}>
+        <div className="space-y-2 pb-6">
+          <Input
+            placeholder="123456"
+            autoComplete="one-time-code"
+            autoFocus
+            required
+            readOnly={loading}
+            value={totpCode}
+            onChange={e => setTotpCode(e.target.value)}
+          />
+        </div>
+        <DialogFooter>
+          <DialogClose asChild>
+            <Button size="sm" type="button" variant="ghost">
+              Cancel
+            </Button>
+          </DialogClose>
+          <Button size="sm" type="submit" disabled={loading}>
+            {loading ? <ReloadIcon className="animate-spin !size-4" /> : <></>}
+            Verify
+          </Button>
+        </DialogFooter>
+      </form> : <></>}
     </DialogContent>
   </Dialog>
 }
@@ -8,6 +8,7 @@ import { Popover, PopoverContent, PopoverTrigger } from '@/components/ui/popover
 import { Select, SelectContent, SelectItem, SelectTrigger, SelectValue } from '@/components/ui/select'
 import { Separator } from '@/components/ui/separator'
 import { useIsMobile } from '@/hooks/use-mobile'
+import { apiFetch, readEvents } from '@/lib/api'
 import { LANGUAGES } from '@/lib/constant'
 import { cn } from '@/lib/utils'
 import { ReloadIcon } from '@radix-ui/react-icons'
@@ -66,6 +67,12 @@ import { toast } from 'sonner'
 
 type BubbleMenuPage = 'main' | 'tone' | 'translate'
 
+type AiAction = {
+  id: string
+  label: string
+  builtin: boolean
+}
+
 const lowlight = createLowlight(all)
 
 // Highlighted selection - not to lose focus when focusing on other element on the page - GitHub
@@ -280,6 +287,20 @@ export default function TiptapEditor({ defaultValue, action, onChange, onSave, o
   // }, [])
 
   const [loadingAi, setLoadingAi] = useState<string>()
+  const [customActions, setCustomActions] = useState<AiAction[]>([])
+  const aiAbortRef = useRef<AbortController>(undefined)
+
+  // Stop streaming AI output into an editor that is going away.
+  useEffect(() => () => aiAbortRef.current?.abort(), [])
+
+  // The user's own and shared rewrite presets, shown after the built-in actions.
+  useEffect(() => {
+    apiFetch('/ai/actions')
+      .then(resp => resp.ok ? resp.json() : { actions: [] })
+      .then(json => setCustomActions((json.actions as AiAction[]).filter(item => !item.builtin)))
+      .catch(() => {})
+  }, [])
+
   const [desktopMenuPage, setDesktopMenuPage] = useState<BubbleMenuPage>('main')
   const [selectedLanguageIndex, setSelectedLanguageIndex] = useState<number>(0)
   const [selectedToneIndex, setSelectedToneIndex] = useState<number>(0)
@@ -340,13 +361,14 @@ export default function TiptapEditor({ defaultValue, action, onChange, onSave, o
   ]
 
   const MAIN_ITEMS = [
-    { key: 'simplify', label: 'Simplify', prompt: 'simplify' },
-    { key: 'fix', label: 'Fix spelling & grammar', prompt: 'fix spelling and grammar' },
+    { key: 'simplify', label: 'Simplify', action: 'simplify' },
+    { key: 'fix', label: 'Fix spelling & grammar', action: 'fix' },
     { key: 'tone', label: 'Rephrase with tone...', hasSubmenu: true },
     { key: 'translate', label: 'Translate to...', hasSubmenu: true },
-    { key: 'shorter', label: 'Make it shorter', prompt: 'make it shorter' },
-    { key: 'longer', label: 'Make it longer', prompt: 'make it longer' },
-    { key: 'emojify', label: 'Emojify', prompt: 'emojify' },
+    { key: 'shorter', label: 'Make it shorter', action: 'shorter' },
+    { key: 'longer', label: 'Make it longer', action: 'longer' },
+    { key: 'emojify', label: 'Emojify', action: 'emojify' },
+    ...customActions.map(custom => ({ key: custom.id, label: custom.label, action: custom.id })),
   ]
 
   const menuHandlerRef = useRef<((e: KeyboardEvent) => void) | null>(null)
@@ -372,7 +394,7 @@ export default function TiptapEditor({ defaultValue, action, onChange, onSave, o
       } else if (e.key === 'ArrowUp') {
         setSelectedToneIndex(prev => prev > 0 ? prev - 1 : prev)
       } else if (e.key === 'Enter') {
-        runAi(`rephrase with ${TONES[selectedToneIndex].toLowerCase()} tone`)
+        runAi('tone', { input: TONES[selectedToneIndex].toLowerCase() })
       } else if (e.key === 'ArrowLeft' || e.key === 'Escape') {
         closeActiveMenu()
       }
@@ -385,7 +407,7 @@ export default function TiptapEditor({ defaultValue, action, onChange, onSave, o
       } else if (e.key === 'ArrowUp') {
         setSelectedLanguageIndex(prev => prev > 0 ? prev - 1 : prev)
       } else if (e.key === 'Enter') {
-        runAi(`translate to ${LANGUAGES[selectedLanguageIndex].name}`)
+        runAi('translate', { input: LANGUAGES[selectedLanguageIndex].name })
       } else if (e.key === 'ArrowLeft' || e.key === 'Escape') {
         closeActiveMenu()
       }
@@ -402,7 +424,7 @@ export default function TiptapEditor({ defaultValue, action, onChange, onSave, o
           })
           return
         }
-        runAi('continue from the selected text!', context)
+        runAi('continue', { context })
       }
       return
     }
@@ -417,8 +439,8 @@ export default function TiptapEditor({ defaultValue, action, onChange, onSave, o
         openToneMenu()
       } else if (item.key === 'translate') {
         openTranslateMenu()
-      } else if (item.prompt) {
-        runAi(item.prompt)
+      } else if (item.action) {
+        runAi(item.action)
       }
     } else if (e.key === 'ArrowRight') {
       const item = MAIN_ITEMS[selectedMainIndex]
@@ -477,40 +499,79 @@ export default function TiptapEditor({ defaultValue, action, onChange, onSave, o
     }
   }
 
-  const runAi = async (prompt: string, context?: string) => {
+  const runAi = async (actionId: string, { input, context }: { input?: string, context?: string } = {}) => {
     if (!editor) return
 
-    setLoadingAi(prompt)
+    setLoadingAi(actionId)
     const selection = getSelectionText() as { from: number, to: number, text: string }
     // Strip base64 image data from context to avoid bloating the /prompt request
     const rawText = context || selection.text
     const selectedText = rawText.replace(/data:image\/[^;]+;base64,[A-Za-z0-9+/=]+/g, '[image]')
 
-    const resp = await fetch(`${process.env.NEXT_PUBLIC_API_URL}/prompt`, {
-      method: 'POST',
-      headers: {
-        'Content-Type': 'application/json',
-      },
-      body: JSON.stringify({
-        prompt: prompt,
-        context: selectedText,
-      }),
-    })
-    setLoadingAi(undefined)
-    closeActiveMenu()
+    aiAbortRef.current?.abort()
+    const abort = new AbortController()
+    aiAbortRef.current = abort
 
-    if (!resp.ok) {
-      toast('Error', {
-        description: await resp.text(),
+    try {
+      const resp = await apiFetch('/prompt/stream', {
+        method: 'POST',
+        headers: {
+          'Content-Type': 'application/json',
+        },
+        body: JSON.stringify({
+          action: actionId,
+          input,
+          context: selectedText,
+        }),
+        signal: abort.signal,
       })
-      return
-    }
+      closeActiveMenu()
+
+      if (!resp.ok) {
+        const json = await resp.json().catch(() => ({}))
+        toast('Error', {
+          description: json.error || 'Something went wrong',
+        })
+        return
+      }
 
-    const json = await resp.json()
-    if (context) {
-      editor.commands.deleteRange({ from: selection.from - 1, to: selection.to })
+      // Replace the selection with the first text that arrives, then keep
+      // appending at the cursor as the rest streams in.
+      let started = false
+      for await (const { event, data } of readEvents(resp)) {
+        const json = JSON.parse(data)
+        if (event === 'error') {
+          toast('Error', {
+            description: json.error,
+          })
+          return
+        }
+        if (event !== 'delta') continue
+
+        const content: string = started ? json.content : json.content.trimStart()
+        if (!content) continue
+        if (!started) {
+          started = true
+          if (context) {
+            editor.commands.deleteRange({ from: selection.from - 1, to: selection.to })
+          } else {
+            editor.commands.deleteRange({ from: selection.from, to: selection.to })
+          }
+        }
+        editor.commands.insertContent(content)
+      }
+    } catch (err) {
+      if (!abort.signal.aborted) {
+        toast('Error', {
+          description: err instanceof Error ? err.message : 'Something went wrong',
+        })
+      }
+    } finally {
+      if (aiAbortRef.current === abort) {
+        aiAbortRef.current = undefined
+        setLoadingAi(undefined)
+      }
     }
-    editor.commands.insertContent(json.result.trim())
   }
 
   const insertImageAsBase64 = useCallback((file: File) => {
@@ -575,7 +636,7 @@ export default function TiptapEditor({ defaultValue, action, onChange, onSave, o
                   })}
                   variant="outline"
                   onClick={() => {
-                    runAi(`rephrase with ${tone.toLowerCase()} tone`)
+                    runAi('tone', { input: tone.toLowerCase() })
                     setOpenDrawer(undefined)
                   }}
                   disabled={!!loadingAi}
@@ -607,7 +668,7 @@ export default function TiptapEditor({ defaultValue, action, onChange, onSave, o
                   })}
                   variant="outline"
                   onClick={() => {
-                    runAi(`translate to ${lang.name}`)
+                    runAi('translate', { input: lang.name })
                     setOpenDrawer(undefined)
                   }}
                   disabled={!!loadingAi}
@@ -648,7 +709,7 @@ export default function TiptapEditor({ defaultValue, action, onChange, onSave, o
                   })}
                   variant="ghost"
                   onClick={() => {
-                    runAi(`rephrase with ${tone.toLowerCase()} tone`)
+                    runAi('tone', { input: tone.toLowerCase() })
                     closeActiveMenu()
                   }}
                   disabled={!!loadingAi}
@@ -674,7 +735,7 @@ export default function TiptapEditor({ defaultValue, action, onChange, onSave, o
                   })}
                   variant="ghost"
                   onClick={() => {
-                    runAi(`translate to ${lang.name}`)
+                    runAi('translate', { input: lang.name })
                     closeActiveMenu()
                   }}
                   disabled={!!loadingAi}
@@ -801,8 +862,8 @@ export default function TiptapEditor({ defaultValue, action, onChange, onSave, o
               {loadingAi === 'simplify' ? <ReloadIcon className="!size-3.5 animate-spin" /> : <Edit3Icon className="!size-3.5" />}
               Simplify
             </Button>
-            <Button size="sm" data-main-index="1" className={cn('gap-2 font-normal w-full justify-start', selectedMainIndex === 1 && 'bg-accent text-accent-foreground')} variant="ghost" onClick={() => runAi('fix spelling and grammar')} disabled={!!loadingAi}>
-              {loadingAi === 'fix spelling and grammar' ? <ReloadIcon className="!size-3.5 animate-spin" /> : <EraserIcon className="!size-3.5" />}
+            <Button size="sm" data-main-index="1" className={cn('gap-2 font-normal w-full justify-start', selectedMainIndex === 1 && 'bg-accent text-accent-foreground')} variant="ghost" onClick={() => runAi('fix')} disabled={!!loadingAi}>
+              {loadingAi === 'fix' ? <ReloadIcon className="!size-3.5 animate-spin" /> : <EraserIcon className="!size-3.5" />}
               Fix spelling & grammar
             </Button>
             <Button
@@ -820,7 +881,7 @@ export default function TiptapEditor({ defaultValue, action, onChange, onSave, o
               }}
             >
               <div className="flex items-center gap-2">
-                {loadingAi?.startsWith('rephrase with') ? <ReloadIcon className="!size-3.5 animate-spin" /> : <DramaIcon className="!size-3.5" />}
+                {loadingAi === 'tone' ? <ReloadIcon className="!size-3.5 animate-spin" /> : <DramaIcon className="!size-3.5" />}
                 <span>Rephrase with tone...</span>
               </div>
               <ChevronRightIcon className="!size-3.5" />
@@ -840,23 +901,29 @@ export default function TiptapEditor({ defaultValue, action, onChange, onSave, o
               }}
             >
               <div className="flex gap-2 items-center">
-                {loadingAi?.startsWith('translate to') ? <ReloadIcon className="!size-3.5 animate-spin" /> : <GlobeIcon className="!size-3.5" />}
+                {loadingAi === 'translate' ? <ReloadIcon className="!size-3.5 animate-spin" /> : <GlobeIcon className="!size-3.5" />}
                 <span>Translate to...</span>
               </div>
               <ChevronRightIcon className="!size-3.5" />
             </Button>
-            <Button size="sm" data-main-index="4" className={cn('gap-2 font-normal w-full justify-start', selectedMainIndex === 4 && 'bg-accent text-accent-foreground')} variant="ghost" onClick={() => runAi('make it shorter')} disabled={!!loadingAi}>
-              {loadingAi === 'make it shorter' ? <ReloadIcon className="!size-3.5 animate-spin" /> : <ListMinusIcon className="!size-3.5" />}
+            <Button size="sm" data-main-index="4" className={cn('gap-2 font-normal w-full justify-start', selectedMainIndex === 4 && 'bg-accent text-accent-foreground')} variant="ghost" onClick={() => runAi('shorter')} disabled={!!loadingAi}>
+              {loadingAi === 'shorter' ? <ReloadIcon className="!size-3.5 animate-spin" /> : <ListMinusIcon className="!size-3.5" />}
               Make it shorter
             </Button>
-            <Button size="sm" data-main-index="5" className={cn('gap-2 font-normal w-full justify-start', selectedMainIndex === 5 && 'bg-accent text-accent-foreground')} variant="ghost" onClick={() => runAi('make it longer')} disabled={!!loadingAi}>
-              {loadingAi === 'make it longer' ? <ReloadIcon className="!size-3.5 animate-spin" /> : <ListPlusIcon className="!size-3.5" />}
+            <Button size="sm" data-main-index="5" className={cn('gap-2 font-normal w-full justify-start', selectedMainIndex === 5 && 'bg-accent text-accent-foreground')} variant="ghost" onClick={() => runAi('longer')} disabled={!!loadingAi}>
+              {loadingAi === 'longer' ? <ReloadIcon className="!size-3.5 animate-spin" /> : <ListPlusIcon className="!size-3.5" />}
               Make it longer
             </Button>
             <Button size="sm" data-main-index="6" className={cn('gap-2 font-normal w-full justify-start', selectedMainIndex === 6 && 'bg-accent text-accent-foreground')} variant="ghost" onClick={() => runAi('emojify')} disabled={!!loadingAi}>
               {loadingAi === 'emojify' ? <ReloadIcon className="!size-3.5 animate-spin" /> : <SmilePlusIcon className="!size-3.5" />}
               Emojify
             </Button>
+            {customActions.map((custom, index) => (
+              <Button key={custom.id} size="sm" data-main-index={7 + index} className={cn('gap-2 font-normal w-full justify-start', selectedMainIndex === 7 + index && 'bg-accent text-accent-foreground')} variant="ghost" onClick={() => runAi(custom.id)} disabled={!!loadingAi}>
+                {loadingAi === custom.id ? <ReloadIcon className="!size-3.5 animate-spin" /> : <SparklesIcon className="!size-3.5" />}
+                {custom.label}
+              </Button>
+            ))}
           </>
         )}
       </div>
@@ -876,9 +943,9 @@ export default function TiptapEditor({ defaultValue, action, onChange, onSave, o
             })
             return
           }
-          runAi('continue from the selected text!', context)
+          runAi('continue', { context })
         }} disabled={!!loadingAi}>
-          {loadingAi === 'continue from the selected text!' ? <ReloadIcon className="!size-3.5 animate-spin" /> : <SparklesIcon className="!size-3.5" />}
+          {loadingAi === 'continue' ? <ReloadIcon className="!size-3.5 animate-spin" /> : <SparklesIcon className="!size-3.5" />}
           Continue
         </Button>
       </div>
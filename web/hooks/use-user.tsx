@@ -10,11 +10,13 @@ import {
   useEffect,
   useState
 } from 'react'
+import { apiFetch } from '@/lib/api'
 
 export type AuthUser = {
   user: {
     id: string,
-    email: string
+    email: string,
+    locale: string | null
   }
 }
 
@@ -39,11 +41,7 @@ export function UserProvider({
   const [user, setUser] = useState<AuthUser | null | undefined>(undefined)
 
   const fetchUser = useCallback(() => {
-    fetch(`${process.env.NEXT_PUBLIC_API_URL}/me`, {
-      headers: {
-        Authorization: `Bearer ${localStorage.getItem('access_token')}`,
-      },
-    }).then(res => {
+    apiFetch('/me').then(res => {
       if (res.ok) {
         res.json().then(setUser)
       } else {
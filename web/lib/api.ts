@@ -0,0 +1,76 @@
+let refreshing: Promise<boolean> | null = null
+
+// Refresh tokens are single-use, so concurrent 401s must share one refresh.
+export function refreshAccessToken(): Promise<boolean> {
+  if (!refreshing) {
+    refreshing = (async () => {
+      const refreshToken = localStorage.getItem('refresh_token')
+      if (!refreshToken) return false
+
+      const res = await fetch(`${process.env.NEXT_PUBLIC_API_URL}/token/refresh`, {
+        method: 'POST',
+        body: JSON.stringify({ refresh_token: refreshToken }),
+        headers: {
+          'Content-Type': 'application/json',
+        },
+      }).catch(() => null)
+      if (!res?.ok) {
+        localStorage.removeItem('access_token')
+        localStorage.removeItem('refresh_token')
+        return false
+      }
+
+      const json = await res.json() as {
+        access_token: string
+        refresh_token: string
+      }
+      localStorage.setItem('access_token', json.access_token)
+      localStorage.setItem('refresh_token', json.refresh_token)
+      return true
+    })().finally(() => {
+      refreshing = null
+    })
+  }
+  return refreshing
+}
+
+export async function apiFetch(path: string, init: RequestInit = {}): Promise<Response> {
+  const send = () => fetch(`${process.env.NEXT_PUBLIC_API_URL}${path}`, {
+    ...init,
+    headers: {
+      ...init.headers,
+      Authorization: `Bearer ${localStorage.getItem('access_token')}`,
+    },
+  })
+
+  const res = await send()
+  if (res.status === 401 && await refreshAccessToken()) {
+    return send()
+  }
+  return res
+}
+
+// Yields the events of a `text/event-stream` response as they arrive.
+export async function* readEvents(res: Response): AsyncGenerator<{ event: string, data: string }> {
+  if (!res.body) return
+  const reader = res.body.pipeThrough(new TextDecoderStream()).getReader()
+  let buffer = ''
+  while (true) {
+    const { done, value } = await reader.read()
+    if (done) return
+    buffer += value.replace(/\r\n?/g, '\n')
+
+    let end
+    while ((end = buffer.indexOf('\n\n')) !== -1) {
+      const block = buffer.slice(0, end)
+      buffer = buffer.slice(end + 2)
+      let event = 'message'
+      const data: string[] = []
+      for (const line of block.split('\n')) {
+        if (line.startsWith('event:')) event = line.slice(6).trim()
+        else if (line.startsWith('data:')) data.push(line.slice(5).replace(/^ /, ''))
+      }
+      if (data.length) yield { event, data: data.join('\n') }
+    }
+  }
+}